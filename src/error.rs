@@ -1,8 +1,7 @@
 use core::fmt::Debug;
 
 use embedded_hal::digital::{Error as DigitalError, ErrorKind as DigitalErrorKind};
-use embedded_hal::i2c::ErrorType;
-use embedded_hal_bus::i2c::AtomicError;
+use embedded_hal::i2c::{Error as I2cError, ErrorKind as I2cErrorKind, ErrorType};
 use thiserror::Error;
 
 /// An error interacting with an expander.
@@ -10,12 +9,40 @@ use thiserror::Error;
 pub enum Error<S: ErrorType> {
     /// An error communicating with an expander.
     Communication(S::Error),
+    /// An expander could not be identified, or is configured in a way the
+    /// driver does not support.
+    Unrecognized,
+    /// An error driving an expander's reset line.
+    Reset,
+    /// An error reading a host pin connected to an expander's interrupt
+    /// output.
+    Interrupt,
+    /// An expander was found to have been reset unexpectedly, losing its
+    /// configuration.
+    DeviceReset,
+    /// The bus was in use by another pin or the interrupt controller of an
+    /// expander, such as one on another thread.
+    Busy,
+    /// An expander's interrupt outputs stayed asserted while servicing
+    /// interrupts, for example because of a level sensitive pin without storm
+    /// protection.
+    Storm,
+    /// An operation is not supported by a pin in its current configuration,
+    /// such as waiting for a level its interrupt sense cannot report.
+    Unsupported,
 }
 
 impl<S: ErrorType<Error = impl Debug>> Debug for Error<S> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Communication(e) => f.debug_tuple("Communication").field(e).finish(),
+            Self::Unrecognized => f.write_str("Unrecognized"),
+            Self::Reset => f.write_str("Reset"),
+            Self::Interrupt => f.write_str("Interrupt"),
+            Self::DeviceReset => f.write_str("DeviceReset"),
+            Self::Busy => f.write_str("Busy"),
+            Self::Storm => f.write_str("Storm"),
+            Self::Unsupported => f.write_str("Unsupported"),
         }
     }
 }
@@ -26,11 +53,11 @@ impl<S: ErrorType<Error = E>, E: Debug> DigitalError for Error<S> {
     }
 }
 
-impl<S: ErrorType> From<AtomicError<S::Error>> for Error<S> {
-    fn from(value: AtomicError<S::Error>) -> Self {
-        match value {
-            AtomicError::Busy => unreachable!(),
-            AtomicError::Other(error) => Self::Communication(error),
+impl<S: ErrorType<Error = E>, E: I2cError> I2cError for Error<S> {
+    fn kind(&self) -> I2cErrorKind {
+        match self {
+            Self::Communication(e) => e.kind(),
+            _ => I2cErrorKind::Other,
         }
     }
 }
@@ -1,62 +1,422 @@
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::ops::{Deref, DerefMut};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
 use embedded_hal::i2c::I2c;
-use embedded_hal_bus::i2c::AtomicDevice;
-use embedded_hal_bus::util::AtomicCell;
 use error::Error;
+use pin::dynamic::{DynPin, DynPins};
+use pin::input::{Floating, Input};
 use pin::interrupt::InterruptController;
-use pin::{Pin, Pins};
+use pin::{Pin, Pins, A0, A7, B7};
+use port::{Port, PortA, PortB};
+use probe::Iocon;
+use registers::{Registers, Snapshot};
+use shared::Shared;
 
 pub mod error;
 pub mod pin;
+pub mod port;
+pub mod probe;
+pub mod share;
+pub mod storage;
 
 pub(crate) mod registers;
+pub(crate) mod shared;
+pub(crate) mod sync;
+#[cfg(test)]
+pub(crate) mod testing;
 
 /// A driver representing a single Microchip MCP23017.
 ///
-/// Generic over an I2C bus `S` and device address `A`.
-pub struct Mcp23017<S: I2c, const A: u8> {
-    cell: AtomicCell<S>,
+/// Generic over an I2C bus `S` and device address `A`, which is checked at
+/// compile time to lie within `0x20..=0x27`, and optionally a reset line `R`
+/// and delay `D`. For devices whose address is only known at runtime, use
+/// [`DynMcp23017`], whose methods are all available on this type too.
+pub struct Mcp23017<S: I2c, const A: u8, R = (), D = ()> {
+    device: DynMcp23017<S, R, D>,
 }
 
 impl<S: I2c, const A: u8> Mcp23017<S, A> {
     /// Construct a new driver for a device accessible over the bus.
     pub fn new(i2c: S) -> Self {
+        const { assert!(is_valid_address(A), "address must lie within 0x20..=0x27") };
+
+        Self {
+            device: DynMcp23017::new(i2c, A),
+        }
+    }
+
+    /// Construct a new driver for a device accessible over a bus shared with
+    /// other devices.
+    ///
+    /// See [`DynMcp23017::new_shared()`].
+    pub fn new_shared(i2c: S) -> Self
+    where
+        S: Copy,
+    {
+        const { assert!(is_valid_address(A), "address must lie within 0x20..=0x27") };
+
+        Self {
+            device: DynMcp23017::new_shared(i2c, A),
+        }
+    }
+
+    /// Attach a line connected to the device's reset pin, and a delay used to
+    /// time reset pulses.
+    ///
+    /// See [`DynMcp23017::with_reset()`].
+    pub fn with_reset<R: OutputPin, D: DelayNs>(self, reset: R, delay: D) -> Mcp23017<S, A, R, D> {
+        Mcp23017 {
+            device: self.device.with_reset(reset, delay),
+        }
+    }
+}
+
+impl<S: I2c, const A: u8, R, D> Mcp23017<S, A, R, D> {
+    /// Detach the reset line and delay from the driver, returning them.
+    pub fn without_reset(self) -> (Mcp23017<S, A>, R, D) {
+        let (device, reset, delay) = self.device.without_reset();
+        (Mcp23017 { device }, reset, delay)
+    }
+
+    /// Destroy the driver, returning the bus.
+    ///
+    /// See [`DynMcp23017::free()`].
+    pub fn free(self) -> S {
+        self.device.free()
+    }
+}
+
+impl<S: I2c, const A: u8, R, D> Deref for Mcp23017<S, A, R, D> {
+    type Target = DynMcp23017<S, R, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl<S: I2c, const A: u8, R, D> DerefMut for Mcp23017<S, A, R, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.device
+    }
+}
+
+/// A driver representing a single Microchip MCP23017, addressed at runtime.
+///
+/// Generic over an I2C bus `S`, and optionally a reset line `R` and delay `D`.
+pub struct DynMcp23017<S: I2c, R = (), D = ()> {
+    shared: Shared<S>,
+    reset: R,
+    delay: D,
+}
+
+impl<S: I2c> DynMcp23017<S> {
+    /// Construct a new driver for a device accessible over the bus at an
+    /// address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address does not lie within `0x20..=0x27`.
+    pub fn new(i2c: S, address: u8) -> Self {
+        assert!(
+            is_valid_address(address),
+            "address must lie within 0x20..=0x27"
+        );
+
+        Self {
+            shared: Shared::new(i2c, address),
+            reset: (),
+            delay: (),
+        }
+    }
+
+    /// Construct a new driver for a device accessible at an address over a
+    /// bus shared with other devices.
+    ///
+    /// The bus is a handle to a bus shared elsewhere, such as one from
+    /// [`share`], and is copied for each access rather than locked by the
+    /// driver. Accessing the bus while it is in use behaves as the handle
+    /// does, for example by blocking or erroring.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address does not lie within `0x20..=0x27`.
+    pub fn new_shared(i2c: S, address: u8) -> Self
+    where
+        S: Copy,
+    {
+        assert!(
+            is_valid_address(address),
+            "address must lie within 0x20..=0x27"
+        );
+
         Self {
-            cell: AtomicCell::new(i2c),
+            shared: Shared::new_shared(i2c, address),
+            reset: (),
+            delay: (),
         }
     }
 
+    /// Attach a line connected to the device's reset pin, and a delay used to
+    /// time reset pulses.
+    ///
+    /// This enables hard resets, both from the driver and, once split, from
+    /// the interrupt controller.
+    pub fn with_reset<R: OutputPin, D: DelayNs>(self, reset: R, delay: D) -> DynMcp23017<S, R, D> {
+        DynMcp23017 {
+            shared: self.shared,
+            reset,
+            delay,
+        }
+    }
+}
+
+impl<S: I2c, R, D> DynMcp23017<S, R, D> {
+    /// Detach the reset line and delay from the driver, returning them.
+    pub fn without_reset(self) -> (DynMcp23017<S>, R, D) {
+        (
+            DynMcp23017 {
+                shared: self.shared,
+                reset: (),
+                delay: (),
+            },
+            self.reset,
+            self.delay,
+        )
+    }
+
+    /// Destroy the driver, returning the bus.
+    ///
+    /// The device is left as it is. To reset it first, release its pins with
+    /// [`Release::Reset`](pin::release::Release::Reset).
+    pub fn free(self) -> S {
+        self.shared.into_inner()
+    }
+
+    /// The device's address on the bus.
+    pub fn address(&self) -> u8 {
+        self.shared.address
+    }
+
+    /// Check whether the device is present on the bus, and if so get its
+    /// configuration.
+    ///
+    /// See [`crate::probe`] for how devices are identified.
+    ///
+    /// Errors if communication with the device fails for any reason other than
+    /// it not acknowledging its address.
+    pub fn probe(&mut self) -> Result<Option<Iocon>, Error<S>> {
+        probe::detect(&mut self.shared.i2c(), self.shared.address)
+    }
+
     /// Extract individually controllable pins and an interrupt controller from the device.
     ///
-    /// Pins A7 and B7 are pre-configured as outputs, as mandated by the
-    /// datasheet.
+    /// The device is assumed to be in its power-on configuration. Pins A7 and
+    /// B7 are pre-configured as outputs, as mandated by the datasheet, and a
+    /// sentinel bit is set in the configuration register, which
+    /// [`InterruptController::check()`] uses to detect device resets.
+    ///
+    /// Errors if communication with the device fails. To check that the
+    /// device is present beforehand, use [`Self::probe()`].
+    pub fn split(&mut self) -> Result<Split<'_, S, R, D>, Error<S>> {
+        self.shared.shadow.store(Snapshot::POWER_ON);
+        let (mut pins, mut interrupt_controller) = unsafe { self.parts() };
+        unsafe {
+            pins.a7.registers.set(Registers::<A7, S>::IODIR, false)?;
+            pins.b7.registers.set(Registers::<B7, S>::IODIR, false)?;
+        }
+        interrupt_controller.arm()?;
+        Ok((pins, interrupt_controller))
+    }
+
+    /// Extract ports, each controlling a bank of pins together, and an
+    /// interrupt controller from the device.
+    ///
+    /// As with [`Self::split()`], the device is assumed to be in its power-on
+    /// configuration, pins A7 and B7 are pre-configured as outputs, and a
+    /// sentinel bit is set in the configuration register.
+    ///
+    /// Errors if communication with the device fails.
+    pub fn split_ports(&mut self) -> Result<SplitPorts<'_, S, R, D>, Error<S>> {
+        let (pins, interrupt_controller) = self.split()?;
+        let shared = pins.a0.registers.shared();
+        Ok(unsafe { (Port::new(shared), Port::new(shared), interrupt_controller) })
+    }
+
+    /// Restore the device to its power-on configuration, then extract
+    /// individually controllable pins and an interrupt controller from it.
+    ///
+    /// Unlike [`Self::split()`], the device's configuration is not assumed, so
+    /// pins are guaranteed to match their types even if the device was
+    /// configured before. All configuration registers are rewritten in a
+    /// single sequential burst, with pins A7 and B7 as outputs driven low, and
+    /// any pending interrupt is cleared. As with [`Self::split()`], a sentinel
+    /// bit is also set in the configuration register, which
+    /// [`InterruptController::check()`] uses to detect device resets.
     ///
     /// Errors if communication with the device fails.
-    pub fn split(&mut self) -> Result<(Pins<S, A>, InterruptController<S, A>), Error<S>> {
+    pub fn reset_and_split(&mut self) -> Result<Split<'_, S, R, D>, Error<S>> {
+        reset(&self.shared, Snapshot::SPLIT)?;
+        Ok(unsafe { self.parts() })
+    }
+
+    /// Extract individually controllable pins and an interrupt controller from
+    /// the device, keeping its current configuration.
+    ///
+    /// Nothing is written to the device, so outputs keep driving their current
+    /// levels. Pins are returned with modes known only at runtime, and may be
+    /// converted into pins of matching types with [`TryFrom`].
+    ///
+    /// Errors if communication with the device fails, or with
+    /// [`Error::Unrecognized`] if the device cannot be identified or is not
+    /// using the paired register layout with sequential addressing.
+    pub fn attach(&mut self) -> Result<Attached<'_, S, R, D>, Error<S>> {
+        let address = self.shared.address;
+        let mut i2c = self.shared.i2c();
+
+        match probe::detect(&mut i2c, address)? {
+            Some(iocon) if !iocon.bank() && !iocon.seqop() => {}
+            _ => return Err(Error::Unrecognized),
+        }
+
+        let mut snapshot = Snapshot([0x00; 0x16]);
+        snapshot.read(&mut i2c, address)?;
+        self.shared.shadow.store(snapshot);
+
+        let shared = &self.shared;
+        let snapshot = &snapshot;
         unsafe {
             Ok((
-                Pins {
-                    a0: Pin::new(AtomicDevice::new(&self.cell)),
-                    a1: Pin::new(AtomicDevice::new(&self.cell)),
-                    a2: Pin::new(AtomicDevice::new(&self.cell)),
-                    a3: Pin::new(AtomicDevice::new(&self.cell)),
-                    a4: Pin::new(AtomicDevice::new(&self.cell)),
-                    a5: Pin::new(AtomicDevice::new(&self.cell)),
-                    a6: Pin::new(AtomicDevice::new(&self.cell)),
-                    a7: Pin::new(AtomicDevice::new(&self.cell)).try_into()?,
-
-                    b0: Pin::new(AtomicDevice::new(&self.cell)),
-                    b1: Pin::new(AtomicDevice::new(&self.cell)),
-                    b2: Pin::new(AtomicDevice::new(&self.cell)),
-                    b3: Pin::new(AtomicDevice::new(&self.cell)),
-                    b4: Pin::new(AtomicDevice::new(&self.cell)),
-                    b5: Pin::new(AtomicDevice::new(&self.cell)),
-                    b6: Pin::new(AtomicDevice::new(&self.cell)),
-                    b7: Pin::new(AtomicDevice::new(&self.cell)).try_into()?,
+                DynPins {
+                    a0: DynPin::new(shared, snapshot),
+                    a1: DynPin::new(shared, snapshot),
+                    a2: DynPin::new(shared, snapshot),
+                    a3: DynPin::new(shared, snapshot),
+                    a4: DynPin::new(shared, snapshot),
+                    a5: DynPin::new(shared, snapshot),
+                    a6: DynPin::new(shared, snapshot),
+                    a7: DynPin::new(shared, snapshot),
+
+                    b0: DynPin::new(shared, snapshot),
+                    b1: DynPin::new(shared, snapshot),
+                    b2: DynPin::new(shared, snapshot),
+                    b3: DynPin::new(shared, snapshot),
+                    b4: DynPin::new(shared, snapshot),
+                    b5: DynPin::new(shared, snapshot),
+                    b6: DynPin::new(shared, snapshot),
+                    b7: DynPin::new(shared, snapshot),
                 },
-                InterruptController::new(AtomicDevice::new(&self.cell)),
+                InterruptController::new(shared, &mut self.reset, &mut self.delay),
             ))
         }
     }
+
+    /// Construct pins and an interrupt controller for the device.
+    ///
+    /// # Safety
+    ///
+    /// The device must be configured to match the pins' types, and no other
+    /// pins or interrupt controller may exist for the device.
+    unsafe fn parts(&mut self) -> (Pins<'_, S>, InterruptController<'_, S, R, D>) {
+        let shared = &self.shared;
+        (
+            Pins {
+                a0: Pin::new(shared),
+                a1: Pin::new(shared),
+                a2: Pin::new(shared),
+                a3: Pin::new(shared),
+                a4: Pin::new(shared),
+                a5: Pin::new(shared),
+                a6: Pin::new(shared),
+                a7: Pin::new(shared),
+
+                b0: Pin::new(shared),
+                b1: Pin::new(shared),
+                b2: Pin::new(shared),
+                b3: Pin::new(shared),
+                b4: Pin::new(shared),
+                b5: Pin::new(shared),
+                b6: Pin::new(shared),
+                b7: Pin::new(shared),
+            },
+            InterruptController::new(shared, &mut self.reset, &mut self.delay),
+        )
+    }
+}
+
+impl<S: I2c, R: OutputPin, D: DelayNs> DynMcp23017<S, R, D> {
+    /// Reset the device by pulsing its reset pin, then restore the
+    /// configuration last written by the driver.
+    ///
+    /// Errors if driving the reset line or communication with the device
+    /// fails.
+    pub fn hard_reset(&mut self) -> Result<(), Error<S>> {
+        hard_reset(&self.shared, &mut self.reset, &mut self.delay)
+    }
+}
+
+/// Rewrite every configuration register of a device, from either register
+/// layout, and clear any pending interrupt.
+pub(crate) fn reset<S: I2c>(shared: &Shared<S>, snapshot: Snapshot) -> Result<(), Error<S>> {
+    let address = shared.address;
+    let mut i2c = shared.i2c();
+
+    // Return to the paired register layout with sequential addressing,
+    // from either layout. Address 0x05 is IOCON in the banked layout, but
+    // GPINTENB in the paired layout, which is about to be rewritten anyway.
+    i2c.write(address, &[0x05, 0x00])?;
+    i2c.write(address, &[Registers::<A0, S>::IOCON, 0x00])?;
+
+    snapshot.apply(&mut i2c, address)?;
+    shared.shadow.store(snapshot);
+
+    // Reading the captured state clears any pending interrupt.
+    let mut intcap_read = [0x00; 2];
+    i2c.write_read(address, &[Registers::<A0, S>::INTCAP], &mut intcap_read)?;
+
+    Ok(())
+}
+
+/// Reset a device by pulsing its reset pin, then restore its configuration.
+pub(crate) fn hard_reset<S: I2c>(
+    shared: &Shared<S>,
+    reset: &mut impl OutputPin,
+    delay: &mut impl DelayNs,
+) -> Result<(), Error<S>> {
+    // The datasheet specifies a minimum reset pulse width of 1 µs.
+    reset.set_low().map_err(|_| Error::Reset)?;
+    delay.delay_us(1);
+    reset.set_high().map_err(|_| Error::Reset)?;
+    delay.delay_us(1);
+
+    shared
+        .shadow
+        .load()
+        .apply(&mut shared.i2c(), shared.address)?;
+    Ok(())
+}
+
+/// Pins and an interrupt controller extracted from a device.
+type Split<'a, S, R, D> = (Pins<'a, S>, InterruptController<'a, S, R, D>);
+
+/// Ports and an interrupt controller extracted from a device.
+type SplitPorts<'a, S, R, D> = (
+    PortA<'a, Input<Floating>, S>,
+    PortB<'a, Input<Floating>, S>,
+    InterruptController<'a, S, R, D>,
+);
+
+/// Pins and an interrupt controller extracted from a device without changing
+/// its configuration.
+type Attached<'a, S, R, D> = (DynPins<'a, S>, InterruptController<'a, S, R, D>);
+
+/// Whether an address lies within the range selectable by the device's
+/// address pins.
+pub(crate) const fn is_valid_address(address: u8) -> bool {
+    address >= 0x20 && address <= 0x27
 }
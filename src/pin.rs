@@ -2,38 +2,45 @@ use core::marker::PhantomData;
 
 use embedded_hal::digital::ErrorType as DigitalErrorType;
 use embedded_hal::i2c::I2c;
-use embedded_hal_bus::i2c::AtomicDevice;
 use input::{Floating, Input};
 use output::Output;
 
 use crate::error::Error;
 use crate::registers::Registers;
+use crate::shared::Shared;
 
+pub mod debounce;
+pub mod dynamic;
+pub mod flex;
+pub mod gesture;
+pub mod group;
 pub mod input;
 pub mod interrupt;
+pub mod open_drain;
 pub mod output;
+pub mod release;
 
 /// An individually controllable pin on an expander.
 ///
 /// All methods may error if communication with the device fails.
-pub struct Pin<'a, I: PinId, M: PinMode, S: I2c, const A: u8> {
+pub struct Pin<'a, I: PinId, M: PinMode, S: I2c> {
     id: PhantomData<I>,
     mode: PhantomData<M>,
 
-    pub(crate) registers: Registers<I, AtomicDevice<'a, S>, A>,
+    pub(crate) registers: Registers<'a, I, S>,
 }
 
-impl<'a, I: PinId, S: I2c, const A: u8> Pin<'a, I, Input<Floating>, S, A> {
-    pub(crate) unsafe fn new(i2c: AtomicDevice<'a, S>) -> Self {
+impl<'a, I: PinId, M: PinMode, S: I2c> Pin<'a, I, M, S> {
+    pub(crate) unsafe fn new(shared: &'a Shared<S>) -> Self {
         Self {
             id: PhantomData,
             mode: PhantomData,
-            registers: Registers::new(i2c),
+            registers: Registers::new(shared),
         }
     }
 }
 
-impl<I: PinId, M: PinMode, S: I2c, const A: u8> DigitalErrorType for Pin<'_, I, M, S, A> {
+impl<I: PinId, M: PinMode, S: I2c> DigitalErrorType for Pin<'_, I, M, S> {
     type Error = Error<S>;
 }
 
@@ -41,23 +48,23 @@ impl<I: PinId, M: PinMode, S: I2c, const A: u8> DigitalErrorType for Pin<'_, I,
 pub trait PinMode {}
 
 /// All pins on an expander, in their default configurations.
-pub struct Pins<'a, S: I2c, const A: u8> {
-    pub a0: Pin<'a, A0, Input<Floating>, S, A>,
-    pub a1: Pin<'a, A1, Input<Floating>, S, A>,
-    pub a2: Pin<'a, A2, Input<Floating>, S, A>,
-    pub a3: Pin<'a, A3, Input<Floating>, S, A>,
-    pub a4: Pin<'a, A4, Input<Floating>, S, A>,
-    pub a5: Pin<'a, A5, Input<Floating>, S, A>,
-    pub a6: Pin<'a, A6, Input<Floating>, S, A>,
-    pub a7: Pin<'a, A7, Output, S, A>,
-    pub b0: Pin<'a, B0, Input<Floating>, S, A>,
-    pub b1: Pin<'a, B1, Input<Floating>, S, A>,
-    pub b2: Pin<'a, B2, Input<Floating>, S, A>,
-    pub b3: Pin<'a, B3, Input<Floating>, S, A>,
-    pub b4: Pin<'a, B4, Input<Floating>, S, A>,
-    pub b5: Pin<'a, B5, Input<Floating>, S, A>,
-    pub b6: Pin<'a, B6, Input<Floating>, S, A>,
-    pub b7: Pin<'a, B7, Output, S, A>,
+pub struct Pins<'a, S: I2c> {
+    pub a0: Pin<'a, A0, Input<Floating>, S>,
+    pub a1: Pin<'a, A1, Input<Floating>, S>,
+    pub a2: Pin<'a, A2, Input<Floating>, S>,
+    pub a3: Pin<'a, A3, Input<Floating>, S>,
+    pub a4: Pin<'a, A4, Input<Floating>, S>,
+    pub a5: Pin<'a, A5, Input<Floating>, S>,
+    pub a6: Pin<'a, A6, Input<Floating>, S>,
+    pub a7: Pin<'a, A7, Output, S>,
+    pub b0: Pin<'a, B0, Input<Floating>, S>,
+    pub b1: Pin<'a, B1, Input<Floating>, S>,
+    pub b2: Pin<'a, B2, Input<Floating>, S>,
+    pub b3: Pin<'a, B3, Input<Floating>, S>,
+    pub b4: Pin<'a, B4, Input<Floating>, S>,
+    pub b5: Pin<'a, B5, Input<Floating>, S>,
+    pub b6: Pin<'a, B6, Input<Floating>, S>,
+    pub b7: Pin<'a, B7, Output, S>,
 }
 
 /// Bank A pin 0
@@ -103,6 +110,7 @@ pub trait PinId {
 }
 
 /// Marker type for a bank/port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Bank {
     /// Bank A
     A,
@@ -110,6 +118,17 @@ pub enum Bank {
     B,
 }
 
+impl Bank {
+    /// The bank's offset from bank A, in register addresses and in state kept
+    /// per bank.
+    pub(crate) const fn offset(self) -> u8 {
+        match self {
+            Bank::A => 0,
+            Bank::B => 1,
+        }
+    }
+}
+
 impl PinId for A0 {
     const BANK: Bank = Bank::A;
     const NUMBER: u8 = 0;
@@ -0,0 +1,278 @@
+//! Software debouncing of input pins.
+//!
+//! Mechanical switches bounce for several milliseconds when pressed or
+//! released, triggering a burst of interrupts. A [`Debouncer`] samples the
+//! state of all pins on a device together, and reports a pin's state only
+//! once it has held for a given period: either a time measured by a
+//! [`Clock`], or a number of consecutive samples.
+//!
+//! All sixteen pins are sampled with a single transaction per tick, using
+//! [`InterruptController::sample()`], so interrupts on other pins are not
+//! lost.
+
+use embedded_hal::i2c::I2c;
+
+use super::input::{Input, InputConfiguration};
+use super::interrupt::{index, Interrupt, InterruptController};
+use super::{Pin, PinId, PinMode};
+use crate::error::Error;
+
+/// A monotonic source of time.
+pub trait Clock {
+    /// The current time, in the units of a debouncing period.
+    ///
+    /// The time may wrap, as long as no period is longer than the wrapping
+    /// interval.
+    fn now(&mut self) -> u32;
+}
+
+/// A [`Clock`] counting samples, for debouncing by sample count.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Samples(u32);
+
+impl Clock for Samples {
+    fn now(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(1);
+        self.0
+    }
+}
+
+/// Marker trait for pin modes whose state may be debounced.
+pub trait InputMode: PinMode {}
+
+impl<C: InputConfiguration> InputMode for Input<C> {}
+impl<C: InputConfiguration> InputMode for Interrupt<C> {}
+
+/// Debounces the state of pins on a device.
+///
+/// Pins are tracked individually, and a change in a pin's state is reported
+/// once the new state has held for the debouncing period. Changes shorter
+/// than the period are ignored.
+pub struct Debouncer<K: Clock = Samples> {
+    clock: K,
+    period: u32,
+
+    /// Tracked pins, per bank.
+    tracked: [u8; 2],
+    /// Tracked pins without a sample yet, per bank.
+    fresh: [u8; 2],
+    /// The state last reported for each pin, per bank.
+    stable: [u8; 2],
+    /// The state last sampled for each pin, per bank.
+    candidate: [u8; 2],
+    /// When each pin's sampled state last changed, indexed by bank then
+    /// number.
+    since: [u32; 16],
+}
+
+impl Debouncer<Samples> {
+    /// Construct a debouncer reporting a state once it has been seen in a
+    /// number of consecutive samples.
+    pub fn with_samples(count: u32) -> Self {
+        Self::new(Samples::default(), count.saturating_sub(1))
+    }
+}
+
+impl<K: Clock> Debouncer<K> {
+    /// Construct a debouncer reporting a state once it has held for a period
+    /// measured by a clock.
+    pub fn new(clock: K, period: u32) -> Self {
+        Self {
+            clock,
+            period,
+            tracked: [0x00; 2],
+            fresh: [0x00; 2],
+            stable: [0x00; 2],
+            candidate: [0x00; 2],
+            since: [0; 16],
+        }
+    }
+
+    /// Start debouncing a pin.
+    ///
+    /// The pin's state at the next sample is taken as stable, without being
+    /// reported as a change.
+    pub fn track<I: PinId, M: InputMode, S: I2c>(&mut self, _pin: &Pin<'_, I, M, S>) {
+        let bank = usize::from(I::BANK.offset());
+        let mask = 1 << I::NUMBER;
+        self.tracked[bank] |= mask;
+        self.fresh[bank] |= mask;
+    }
+
+    /// Stop debouncing a pin.
+    pub fn untrack<I: PinId, M: InputMode, S: I2c>(&mut self, _pin: &Pin<'_, I, M, S>) {
+        self.tracked[usize::from(I::BANK.offset())] &= !(1 << I::NUMBER);
+    }
+
+    /// Sample all pins on a device with a single transaction, and debounce
+    /// them.
+    ///
+    /// This should be called periodically, at an interval well below the
+    /// debouncing period. Interrupts pending on the device are recorded by
+    /// the controller, as by [`InterruptController::sample()`].
+    ///
+    /// Errors if communication with the device fails.
+    pub fn tick<S: I2c, R, D, A, B>(
+        &mut self,
+        controller: &mut InterruptController<'_, S, R, D, A, B>,
+    ) -> Result<Changes, Error<S>> {
+        let state = controller.sample()?;
+        Ok(self.update(state))
+    }
+
+    /// Debounce a sample of the state of banks A and B, taken by other means.
+    pub fn update(&mut self, state: [u8; 2]) -> Changes {
+        let now = self.clock.now();
+        let mut changes = Changes::default();
+
+        for (bank, sample) in state.into_iter().enumerate() {
+            let fresh = self.fresh[bank] & self.tracked[bank];
+            self.stable[bank] = self.stable[bank] & !fresh | sample & fresh;
+            self.candidate[bank] = self.candidate[bank] & !fresh | sample & fresh;
+            self.fresh[bank] = 0x00;
+
+            for number in 0..8 {
+                let mask = 1 << number;
+                if self.tracked[bank] & mask == 0 {
+                    continue;
+                }
+
+                let pin = bank * 8 + number;
+                if (sample ^ self.candidate[bank]) & mask != 0 {
+                    self.candidate[bank] ^= mask;
+                    self.since[pin] = now;
+                }
+
+                let settled = now.wrapping_sub(self.since[pin]) >= self.period;
+                if (self.candidate[bank] ^ self.stable[bank]) & mask != 0 && settled {
+                    self.stable[bank] ^= mask;
+                    changes.changed |= 1 << pin;
+                }
+            }
+        }
+
+        changes.levels = u16::from_le_bytes(self.stable);
+        changes
+    }
+
+    /// The debounced state of a pin.
+    pub fn is_high<I: PinId>(&self) -> bool {
+        self.stable[usize::from(I::BANK.offset())] & (1 << I::NUMBER) != 0
+    }
+
+    /// The debounced state of all pins, with bits indexed by bank then
+    /// number: bit 0 for A0 through to bit 15 for B7.
+    pub fn levels(&self) -> u16 {
+        u16::from_le_bytes(self.stable)
+    }
+}
+
+/// Changes in debounced pin states, from a single sample.
+///
+/// Bits are indexed by bank then number: bit 0 for A0 through to bit 15 for
+/// B7.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    changed: u16,
+    levels: u16,
+}
+
+impl Changes {
+    /// The new debounced state of a pin, if it changed.
+    pub fn get<I: PinId>(&self) -> Option<bool> {
+        let mask = 1 << index::<I>();
+        (self.changed & mask != 0).then_some(self.levels & mask != 0)
+    }
+
+    /// Whether no pin changed.
+    pub fn is_empty(&self) -> bool {
+        self.changed == 0
+    }
+
+    /// The pins that changed.
+    pub fn changed(&self) -> u16 {
+        self.changed
+    }
+
+    /// The debounced state of all pins.
+    pub fn levels(&self) -> u16 {
+        self.levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::pin::{A1, B2};
+    use crate::shared::Shared;
+    use crate::testing::{pin, Manual, NoBus};
+
+    #[test]
+    fn settles_after_count() {
+        let shared = Shared::new(NoBus, 0x20);
+        let mut debouncer = Debouncer::with_samples(3);
+        debouncer.track(&pin::<A1>(&shared));
+        debouncer.update([0x00, 0x00]);
+
+        assert!(debouncer.update([0x02, 0x00]).is_empty());
+        assert!(debouncer.update([0x02, 0x00]).is_empty());
+        let changes = debouncer.update([0x02, 0x00]);
+        assert_eq!(changes.get::<A1>(), Some(true));
+        assert_eq!(changes.changed(), 0x0002);
+        assert!(debouncer.is_high::<A1>());
+        assert!(debouncer.update([0x02, 0x00]).is_empty());
+    }
+
+    #[test]
+    fn rejects_bounces() {
+        let shared = Shared::new(NoBus, 0x20);
+        let mut debouncer = Debouncer::with_samples(3);
+        debouncer.track(&pin::<A1>(&shared));
+        debouncer.update([0x00, 0x00]);
+
+        for state in [0x02, 0x00, 0x02, 0x02, 0x00, 0x02, 0x00, 0x00] {
+            assert!(debouncer.update([state, 0x00]).is_empty());
+        }
+        assert!(!debouncer.is_high::<A1>());
+    }
+
+    #[test]
+    fn first_sample_is_not_a_change() {
+        let shared = Shared::new(NoBus, 0x20);
+        let mut debouncer = Debouncer::with_samples(1);
+        debouncer.track(&pin::<A1>(&shared));
+        assert!(debouncer.update([0x02, 0x00]).is_empty());
+        assert!(debouncer.is_high::<A1>());
+
+        debouncer.track(&pin::<B2>(&shared));
+        assert!(debouncer.update([0x02, 0x04]).is_empty());
+        assert!(debouncer.is_high::<B2>());
+        assert_eq!(debouncer.levels(), 0x0402);
+    }
+
+    #[test]
+    fn ignores_untracked_pins() {
+        let shared = Shared::new(NoBus, 0x20);
+        let mut debouncer = Debouncer::with_samples(1);
+        debouncer.track(&pin::<A1>(&shared));
+        debouncer.update([0x00, 0x00]);
+        assert!(debouncer.update([0xFD, 0xFF]).is_empty());
+    }
+
+    #[test]
+    fn survives_clock_wraparound() {
+        let time = Cell::new(u32::MAX - 5);
+        let shared = Shared::new(NoBus, 0x20);
+        let mut debouncer = Debouncer::new(Manual(&time), 10);
+        debouncer.track(&pin::<A1>(&shared));
+        debouncer.update([0x00, 0x00]);
+
+        assert!(debouncer.update([0x02, 0x00]).is_empty());
+        time.set(3);
+        assert!(debouncer.update([0x02, 0x00]).is_empty());
+        time.set(4);
+        assert_eq!(debouncer.update([0x02, 0x00]).get::<A1>(), Some(true));
+    }
+}
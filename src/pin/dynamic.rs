@@ -0,0 +1,211 @@
+//! Pins with modes known only at runtime.
+//!
+//! Dynamic pins are obtained when attaching to a device that has already been
+//! configured, and reflect that configuration without changing it. Each can be
+//! converted into a pin of the matching type, with the conversion failing if
+//! the pin's configuration does not match.
+
+use core::marker::PhantomData;
+
+use embedded_hal::digital::{ErrorType as DigitalErrorType, InputPin, OutputPin};
+use embedded_hal::i2c::I2c;
+
+use super::input::{Floating, Input, PullUp};
+use super::interrupt::{Interrupt, Sense};
+use super::output::Output;
+use super::{InputPinId, Pin, PinId};
+use super::{A0, A1, A2, A3, A4, A5, A6, A7, B0, B1, B2, B3, B4, B5, B6, B7};
+use crate::error::Error;
+use crate::registers::{Registers, Snapshot};
+use crate::shared::Shared;
+
+/// A pin's direction and function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DynMode {
+    /// An input.
+    Input,
+    /// An input triggering interrupts.
+    Interrupt(Sense),
+    /// An output.
+    Output,
+}
+
+/// A pin's configuration, as read from a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DynConfiguration {
+    /// The pin's direction and function.
+    pub mode: DynMode,
+    /// Whether the internal pull up is connected.
+    pub pull_up: bool,
+    /// Whether the pin's input polarity is inverted.
+    pub inverted: bool,
+    /// The level latched for output.
+    pub latch: bool,
+}
+
+impl DynConfiguration {
+    fn read<I: PinId, S: I2c>(snapshot: &Snapshot) -> Self {
+        let mode = if !snapshot.get::<I, S>(Registers::<I, S>::IODIR) {
+            DynMode::Output
+        } else if !snapshot.get::<I, S>(Registers::<I, S>::GPINTEN) {
+            DynMode::Input
+        } else if !snapshot.get::<I, S>(Registers::<I, S>::INTCON) {
+            DynMode::Interrupt(Sense::Edge)
+        } else if snapshot.get::<I, S>(Registers::<I, S>::DEFVAL) {
+            DynMode::Interrupt(Sense::Low)
+        } else {
+            DynMode::Interrupt(Sense::High)
+        };
+
+        Self {
+            mode,
+            pull_up: snapshot.get::<I, S>(Registers::<I, S>::GPPU),
+            inverted: snapshot.get::<I, S>(Registers::<I, S>::IOPOL),
+            latch: snapshot.get::<I, S>(Registers::<I, S>::OLAT),
+        }
+    }
+}
+
+/// An individually controllable pin on an expander, in a mode known only at
+/// runtime.
+///
+/// All methods may error if communication with the device fails.
+pub struct DynPin<'a, I: PinId, S: I2c> {
+    configuration: DynConfiguration,
+
+    pub(crate) registers: Registers<'a, I, S>,
+}
+
+impl<'a, I: PinId, S: I2c> DynPin<'a, I, S> {
+    pub(crate) unsafe fn new(shared: &'a Shared<S>, snapshot: &Snapshot) -> Self {
+        Self {
+            configuration: DynConfiguration::read::<I, S>(snapshot),
+            registers: Registers::new(shared),
+        }
+    }
+
+    /// The pin's configuration.
+    pub fn configuration(&self) -> DynConfiguration {
+        self.configuration
+    }
+
+    /// Convert into a pin of a given type if, and only if, the pin's
+    /// configuration matches it exactly.
+    fn convert<M: super::PinMode>(
+        self,
+        mode: DynMode,
+        pull_up: bool,
+    ) -> Result<Pin<'a, I, M, S>, Self> {
+        let configuration = self.configuration;
+        if configuration.mode == mode && configuration.pull_up == pull_up && !configuration.inverted
+        {
+            Ok(Pin {
+                id: PhantomData,
+                mode: PhantomData,
+                registers: self.registers,
+            })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<I: PinId, S: I2c> DigitalErrorType for DynPin<'_, I, S> {
+    type Error = Error<S>;
+}
+
+impl<I: PinId, S: I2c> InputPin for DynPin<'_, I, S> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(unsafe { self.registers.get(Registers::<I, S>::GPIO)? })
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|x| !x)
+    }
+}
+
+/// Setting the level of a pin not configured as an output only changes its
+/// latched level.
+impl<I: PinId, S: I2c> OutputPin for DynPin<'_, I, S> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        unsafe { self.registers.set(Registers::<I, S>::OLAT, false)? };
+        self.configuration.latch = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        unsafe { self.registers.set(Registers::<I, S>::OLAT, true)? };
+        self.configuration.latch = true;
+        Ok(())
+    }
+}
+
+/// Converts if the pin is configured as an output, regardless of its pull up.
+impl<'a, I: PinId, S: I2c> TryFrom<DynPin<'a, I, S>> for Pin<'a, I, Output, S> {
+    type Error = DynPin<'a, I, S>;
+
+    fn try_from(pin: DynPin<'a, I, S>) -> Result<Self, Self::Error> {
+        let pull_up = pin.configuration.pull_up;
+        pin.convert(DynMode::Output, pull_up)
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> TryFrom<DynPin<'a, I, S>> for Pin<'a, I, Input<Floating>, S> {
+    type Error = DynPin<'a, I, S>;
+
+    fn try_from(pin: DynPin<'a, I, S>) -> Result<Self, Self::Error> {
+        pin.convert(DynMode::Input, false)
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> TryFrom<DynPin<'a, I, S>> for Pin<'a, I, Input<PullUp>, S> {
+    type Error = DynPin<'a, I, S>;
+
+    fn try_from(pin: DynPin<'a, I, S>) -> Result<Self, Self::Error> {
+        pin.convert(DynMode::Input, true)
+    }
+}
+
+/// Converts if the pin triggers interrupts, with any sense.
+impl<'a, I: InputPinId, S: I2c> TryFrom<DynPin<'a, I, S>> for Pin<'a, I, Interrupt<Floating>, S> {
+    type Error = DynPin<'a, I, S>;
+
+    fn try_from(pin: DynPin<'a, I, S>) -> Result<Self, Self::Error> {
+        match pin.configuration.mode {
+            mode @ DynMode::Interrupt(_) => pin.convert(mode, false),
+            _ => Err(pin),
+        }
+    }
+}
+
+/// Converts if the pin triggers interrupts, with any sense.
+impl<'a, I: InputPinId, S: I2c> TryFrom<DynPin<'a, I, S>> for Pin<'a, I, Interrupt<PullUp>, S> {
+    type Error = DynPin<'a, I, S>;
+
+    fn try_from(pin: DynPin<'a, I, S>) -> Result<Self, Self::Error> {
+        match pin.configuration.mode {
+            mode @ DynMode::Interrupt(_) => pin.convert(mode, true),
+            _ => Err(pin),
+        }
+    }
+}
+
+/// All pins on an expander, in the configurations read when attaching.
+pub struct DynPins<'a, S: I2c> {
+    pub a0: DynPin<'a, A0, S>,
+    pub a1: DynPin<'a, A1, S>,
+    pub a2: DynPin<'a, A2, S>,
+    pub a3: DynPin<'a, A3, S>,
+    pub a4: DynPin<'a, A4, S>,
+    pub a5: DynPin<'a, A5, S>,
+    pub a6: DynPin<'a, A6, S>,
+    pub a7: DynPin<'a, A7, S>,
+    pub b0: DynPin<'a, B0, S>,
+    pub b1: DynPin<'a, B1, S>,
+    pub b2: DynPin<'a, B2, S>,
+    pub b3: DynPin<'a, B3, S>,
+    pub b4: DynPin<'a, B4, S>,
+    pub b5: DynPin<'a, B5, S>,
+    pub b6: DynPin<'a, B6, S>,
+    pub b7: DynPin<'a, B7, S>,
+}
@@ -0,0 +1,198 @@
+//! Configurations for using pins as both inputs and outputs.
+//!
+//! A flexible pin switches direction and pull up in place, without changing
+//! type, which suits protocols that turn a line around many times per
+//! transaction. Its configuration is tracked by the driver, so switching to
+//! the current direction, or checking it, costs no bus traffic.
+//!
+//! Only pins that may be configured as inputs can be used as flexible pins.
+
+use core::marker::PhantomData;
+
+use embedded_hal::digital::{InputPin, OutputPin, PinState, StatefulOutputPin};
+use embedded_hal::i2c::I2c;
+
+use super::input::{Floating, Input, InputConfiguration, PullUp};
+use super::output::Output;
+use super::{InputPinId, Pin, PinMode};
+use crate::error::Error;
+use crate::registers::Registers;
+
+/// Marker type for pins set as flexible inputs and outputs.
+pub struct Flex;
+
+impl PinMode for Flex {}
+
+impl<I: InputPinId, S: I2c> Pin<'_, I, Flex, S> {
+    /// Whether the pin is currently an output.
+    pub fn is_output(&self) -> bool {
+        !self.registers.cached(Registers::<I, S>::IODIR)
+    }
+
+    /// Whether the internal pull up is currently connected.
+    pub fn is_pull_up(&self) -> bool {
+        self.registers.cached(Registers::<I, S>::GPPU)
+    }
+
+    /// Switch the pin to an input.
+    pub fn set_as_input(&mut self) -> Result<(), Error<S>> {
+        unsafe { self.registers.update(Registers::<I, S>::IODIR, true)? };
+        Ok(())
+    }
+
+    /// Switch the pin to an output, driving the level held in its output
+    /// latch.
+    pub fn set_as_output(&mut self) -> Result<(), Error<S>> {
+        unsafe { self.registers.update(Registers::<I, S>::IODIR, false)? };
+        Ok(())
+    }
+
+    /// Switch the pin to an output, driving a given level.
+    ///
+    /// The output latch is written before the direction, so the pin drives the
+    /// given level from the moment it becomes an output.
+    pub fn set_as_output_with_state(&mut self, state: PinState) -> Result<(), Error<S>> {
+        unsafe {
+            self.registers
+                .update(Registers::<I, S>::OLAT, state == PinState::High)?
+        };
+        self.set_as_output()
+    }
+
+    /// Connect or disconnect the internal pull up.
+    pub fn set_pull_up(&mut self, pull_up: bool) -> Result<(), Error<S>> {
+        unsafe { self.registers.update(Registers::<I, S>::GPPU, pull_up)? };
+        Ok(())
+    }
+}
+
+/// Setting the level of the pin while it is an input only changes its latched
+/// level, which is driven once it becomes an output.
+impl<I: InputPinId, S: I2c> OutputPin for Pin<'_, I, Flex, S> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        unsafe { self.registers.update(Registers::<I, S>::OLAT, false)? };
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        unsafe { self.registers.update(Registers::<I, S>::OLAT, true)? };
+        Ok(())
+    }
+}
+
+impl<I: InputPinId, S: I2c> StatefulOutputPin for Pin<'_, I, Flex, S> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.registers.cached(Registers::<I, S>::OLAT))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_set_high().map(|x| !x)
+    }
+}
+
+impl<I: InputPinId, S: I2c> InputPin for Pin<'_, I, Flex, S> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(unsafe { self.registers.get(Registers::<I, S>::GPIO)? })
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|x| !x)
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> Pin<'a, I, Input<C>, S> {
+    /// Reconfigure the pin as a flexible pin, starting as an input with its
+    /// pull up configuration unchanged.
+    pub fn into_flex(self) -> Pin<'a, I, Flex, S> {
+        Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        }
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> Pin<'a, I, Output, S> {
+    /// Reconfigure the pin as a flexible pin, starting as an output driving
+    /// its current level.
+    pub fn into_flex(self) -> Pin<'a, I, Flex, S> {
+        Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        }
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> Pin<'a, I, Flex, S> {
+    /// Reconfigure the pin as an input, with the internal pull up disconnected.
+    pub fn into_floating_input(mut self) -> Result<Pin<'a, I, Input<Floating>, S>, Error<S>> {
+        self.set_pull_up(false)?;
+        self.set_as_input()?;
+        Ok(Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        })
+    }
+
+    /// Reconfigure the pin as an input, with the internal pull up connected.
+    pub fn into_pull_up_input(mut self) -> Result<Pin<'a, I, Input<PullUp>, S>, Error<S>> {
+        self.set_pull_up(true)?;
+        self.set_as_input()?;
+        Ok(Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        })
+    }
+
+    /// Reconfigure the pin as a push pull output, driving the level held in its
+    /// output latch.
+    pub fn into_push_pull_output(mut self) -> Result<Pin<'a, I, Output, S>, Error<S>> {
+        self.set_as_output()?;
+        Ok(Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        })
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> From<Pin<'a, I, Input<C>, S>>
+    for Pin<'a, I, Flex, S>
+{
+    fn from(input: Pin<'a, I, Input<C>, S>) -> Self {
+        input.into_flex()
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> From<Pin<'a, I, Output, S>> for Pin<'a, I, Flex, S> {
+    fn from(output: Pin<'a, I, Output, S>) -> Self {
+        output.into_flex()
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> TryFrom<Pin<'a, I, Flex, S>> for Pin<'a, I, Input<Floating>, S> {
+    type Error = Error<S>;
+
+    fn try_from(flex: Pin<'a, I, Flex, S>) -> Result<Self, Self::Error> {
+        flex.into_floating_input()
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> TryFrom<Pin<'a, I, Flex, S>> for Pin<'a, I, Input<PullUp>, S> {
+    type Error = Error<S>;
+
+    fn try_from(flex: Pin<'a, I, Flex, S>) -> Result<Self, Self::Error> {
+        flex.into_pull_up_input()
+    }
+}
+
+impl<'a, I: InputPinId, S: I2c> TryFrom<Pin<'a, I, Flex, S>> for Pin<'a, I, Output, S> {
+    type Error = Error<S>;
+
+    fn try_from(flex: Pin<'a, I, Flex, S>) -> Result<Self, Self::Error> {
+        flex.into_push_pull_output()
+    }
+}
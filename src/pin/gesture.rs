@@ -0,0 +1,430 @@
+//! Recognition of button gestures.
+//!
+//! A [`Recognizer`] turns the debounced state of pins connected to buttons
+//! into [`Gesture`]s: clicks, double-clicks, long presses, and repeats while a
+//! long press is held. Timing is measured by a [`Clock`], in the same units as
+//! the thresholds given in [`Timing`].
+//!
+//! States are typically taken from a [`Debouncer`](super::debounce::Debouncer)
+//! on every tick, but may also be given for individual pins as interrupts are
+//! checked.
+
+use embedded_hal::i2c::I2c;
+
+use super::debounce::{Clock, InputMode};
+use super::interrupt::index;
+use super::{Pin, PinId};
+
+/// A button gesture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    /// A press and release, not followed by another press within the
+    /// double-click period.
+    Click,
+    /// Two presses and releases within the double-click period.
+    DoubleClick,
+    /// A press held for the long press period.
+    LongPress,
+    /// A long press still held after another repeat period.
+    Repeat,
+}
+
+/// Thresholds for recognizing gestures, in units of the recognizer's
+/// [`Clock`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// The longest time from a release to the next press for the presses to
+    /// form a double-click, or [`None`] to report every click as soon as the
+    /// button is released.
+    pub double_click: Option<u32>,
+    /// The shortest press reported as a long press.
+    pub long_press: u32,
+    /// The time between repeats while a long press is held, or [`None`] not
+    /// to repeat.
+    pub repeat: Option<u32>,
+}
+
+/// The state of a button.
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    /// Pressed, for the first or second time in a double-click.
+    Pressed {
+        since: u32,
+        second: bool,
+    },
+    /// Held past the long press period, last reported at a time.
+    Held {
+        last: u32,
+    },
+    /// Released after a first press, waiting for a second.
+    Released {
+        since: u32,
+    },
+}
+
+/// Recognizes gestures on buttons connected to pins of a device.
+pub struct Recognizer<K: Clock> {
+    clock: K,
+    timing: Timing,
+
+    /// Tracked pins, with bits indexed by bank then number.
+    tracked: u16,
+    /// The state of each pin while its button is pressed.
+    active: u16,
+    /// The last state given for each pin.
+    levels: u16,
+    /// The state of each pin's button, indexed by bank then number.
+    states: [State; 16],
+}
+
+impl<K: Clock> Recognizer<K> {
+    /// Construct a recognizer, measuring time with a clock.
+    pub fn new(clock: K, timing: Timing) -> Self {
+        Self {
+            clock,
+            timing,
+            tracked: 0x0000,
+            active: 0x0000,
+            levels: 0x0000,
+            states: [State::Idle; 16],
+        }
+    }
+
+    /// Start recognizing gestures on a pin, given its state while the button
+    /// is pressed.
+    ///
+    /// The button is taken to be released until a state is given.
+    pub fn track<I: PinId, M: InputMode, S: I2c>(
+        &mut self,
+        _pin: &Pin<'_, I, M, S>,
+        pressed: bool,
+    ) {
+        let mask = 1 << index::<I>();
+        self.tracked |= mask;
+        if pressed {
+            self.active |= mask;
+            self.levels &= !mask;
+        } else {
+            self.active &= !mask;
+            self.levels |= mask;
+        }
+        self.states[index::<I>()] = State::Idle;
+    }
+
+    /// Stop recognizing gestures on a pin.
+    pub fn untrack<I: PinId, M: InputMode, S: I2c>(&mut self, _pin: &Pin<'_, I, M, S>) {
+        self.tracked &= !(1 << index::<I>());
+    }
+
+    /// Update the state of all pins, with bits indexed by bank then number,
+    /// as given by [`Debouncer::levels()`].
+    ///
+    /// This should be called periodically, even if no state has changed, as
+    /// some gestures are recognized by time passing.
+    ///
+    /// [`Debouncer::levels()`]: super::debounce::Debouncer::levels
+    pub fn update(&mut self, levels: u16) -> Gestures {
+        self.levels = levels;
+        let now = self.clock.now();
+        let pressed = !(self.levels ^ self.active);
+
+        let mut gestures = Gestures::default();
+        for (pin, state) in self.states.iter_mut().enumerate() {
+            if self.tracked & 1 << pin != 0 {
+                gestures.0[pin] = step(state, &self.timing, now, pressed & 1 << pin != 0);
+            }
+        }
+        gestures
+    }
+
+    /// Update the state of a single pin, for example as captured at an
+    /// interrupt, keeping the last state given for all others.
+    pub fn update_pin<I: PinId>(&mut self, level: bool) -> Gestures {
+        let mask = 1 << index::<I>();
+        let levels = if level {
+            self.levels | mask
+        } else {
+            self.levels & !mask
+        };
+        self.update(levels)
+    }
+}
+
+/// Advance a button's state, returning the gestures recognized, in order.
+///
+/// A long second press of a double-click completes the click before it, so
+/// two gestures may be recognized at once.
+fn step(state: &mut State, timing: &Timing, now: u32, pressed: bool) -> [Option<Gesture>; 2] {
+    let (next, gesture) = match (*state, pressed) {
+        (State::Idle, false) => (State::Idle, None),
+        (State::Idle, true) => (
+            State::Pressed {
+                since: now,
+                second: false,
+            },
+            None,
+        ),
+
+        (State::Pressed { since, second }, true)
+            if now.wrapping_sub(since) >= timing.long_press =>
+        {
+            *state = State::Held { last: now };
+            return long_press(second);
+        }
+        (State::Pressed { .. }, true) => (*state, None),
+        (State::Pressed { since, second }, false)
+            if now.wrapping_sub(since) >= timing.long_press =>
+        {
+            *state = State::Idle;
+            return long_press(second);
+        }
+        (State::Pressed { second: true, .. }, false) => (State::Idle, Some(Gesture::DoubleClick)),
+        (State::Pressed { second: false, .. }, false) => match timing.double_click {
+            Some(_) => (State::Released { since: now }, None),
+            None => (State::Idle, Some(Gesture::Click)),
+        },
+
+        (State::Held { last }, true) => match timing.repeat {
+            Some(repeat) if now.wrapping_sub(last) >= repeat => {
+                (State::Held { last: now }, Some(Gesture::Repeat))
+            }
+            _ => (*state, None),
+        },
+        (State::Held { .. }, false) => (State::Idle, None),
+
+        (State::Released { since }, pressed) => {
+            let expired = match timing.double_click {
+                Some(double_click) => now.wrapping_sub(since) > double_click,
+                None => true,
+            };
+            match (expired, pressed) {
+                (true, false) => (State::Idle, Some(Gesture::Click)),
+                (true, true) => (
+                    State::Pressed {
+                        since: now,
+                        second: false,
+                    },
+                    Some(Gesture::Click),
+                ),
+                (false, false) => (*state, None),
+                (false, true) => (
+                    State::Pressed {
+                        since: now,
+                        second: true,
+                    },
+                    None,
+                ),
+            }
+        }
+    };
+
+    *state = next;
+    [gesture, None]
+}
+
+/// The gestures recognized when a press becomes long, completing the click
+/// before it if it is the second press of a double-click.
+fn long_press(second: bool) -> [Option<Gesture>; 2] {
+    if second {
+        [Some(Gesture::Click), Some(Gesture::LongPress)]
+    } else {
+        [Some(Gesture::LongPress), None]
+    }
+}
+
+/// Gestures recognized in a single update.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Gestures([[Option<Gesture>; 2]; 16]);
+
+impl Gestures {
+    /// The last gesture recognized on a pin, if any.
+    ///
+    /// A click followed by a long press may be recognized in a single
+    /// update, in which case this is the long press. Use [`Self::iter()`] to
+    /// see both.
+    pub fn get<I: PinId>(&self) -> Option<Gesture> {
+        let [first, second] = self.0[index::<I>()];
+        second.or(first)
+    }
+
+    /// Whether no gesture was recognized.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().flatten().all(Option::is_none)
+    }
+
+    /// Iterate over the gestures recognized, in order, with the index of each
+    /// pin by bank then number, from A0 to B7.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Gesture)> + '_ {
+        self.0.iter().enumerate().flat_map(|(pin, gestures)| {
+            gestures
+                .iter()
+                .filter_map(move |gesture| gesture.map(|gesture| (pin, gesture)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::pin::{A1, B2};
+    use crate::shared::Shared;
+    use crate::testing::{pin, Manual, NoBus};
+
+    use Gesture::{Click, DoubleClick, LongPress, Repeat};
+
+    const TIMING: Timing = Timing {
+        double_click: Some(200),
+        long_press: 500,
+        repeat: Some(100),
+    };
+
+    /// Updates at a time, with the button pressed or not, and the gestures
+    /// expected.
+    type Steps = &'static [(u32, bool, &'static [Gesture])];
+
+    const CASES: &[(&str, Timing, Steps)] = &[
+        (
+            "click",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (50, false, &[]),
+                (250, false, &[]),
+                (251, false, &[Click]),
+                (1000, false, &[]),
+            ],
+        ),
+        (
+            "click without double-clicks",
+            Timing {
+                double_click: None,
+                ..TIMING
+            },
+            &[(0, true, &[]), (50, false, &[Click])],
+        ),
+        (
+            "double-click",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (50, false, &[]),
+                (100, true, &[]),
+                (150, false, &[DoubleClick]),
+                (1000, false, &[]),
+            ],
+        ),
+        (
+            "click then press after double-click period",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (50, false, &[]),
+                (300, true, &[Click]),
+                (350, false, &[]),
+                (551, false, &[Click]),
+            ],
+        ),
+        (
+            "long press and repeat",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (499, true, &[]),
+                (500, true, &[LongPress]),
+                (599, true, &[]),
+                (600, true, &[Repeat]),
+                (700, true, &[Repeat]),
+                (710, false, &[]),
+                (2000, false, &[]),
+            ],
+        ),
+        (
+            "long press without repeat",
+            Timing {
+                repeat: None,
+                ..TIMING
+            },
+            &[(0, true, &[]), (500, true, &[LongPress]), (1000, true, &[])],
+        ),
+        (
+            "long press released between updates",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (600, false, &[LongPress]),
+                (1000, false, &[]),
+            ],
+        ),
+        (
+            "long second press",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (50, false, &[]),
+                (100, true, &[]),
+                (600, true, &[Click, LongPress]),
+                (700, true, &[Repeat]),
+                (710, false, &[]),
+            ],
+        ),
+        (
+            "long second press released between updates",
+            TIMING,
+            &[
+                (0, true, &[]),
+                (50, false, &[]),
+                (100, true, &[]),
+                (700, false, &[Click, LongPress]),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn recognizes_gestures() {
+        let shared = Shared::new(NoBus, 0x20);
+        for (name, timing, steps) in CASES {
+            let time = Cell::new(0);
+            let mut recognizer = Recognizer::new(Manual(&time), *timing);
+            recognizer.track(&pin::<A1>(&shared), true);
+
+            for (now, pressed, expected) in *steps {
+                time.set(*now);
+                let gestures = recognizer.update(if *pressed { 0x0002 } else { 0x0000 });
+                assert!(
+                    gestures
+                        .iter()
+                        .eq(expected.iter().map(|gesture| (1, *gesture))),
+                    "{name}, at {now}: {gestures:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tracks_pins_independently() {
+        let shared = Shared::new(NoBus, 0x20);
+        let time = Cell::new(0);
+        let mut recognizer = Recognizer::new(Manual(&time), TIMING);
+        recognizer.track(&pin::<A1>(&shared), false);
+        recognizer.track(&pin::<B2>(&shared), true);
+
+        assert!(recognizer.update(0x0002).is_empty());
+        time.set(10);
+        assert!(recognizer.update_pin::<A1>(false).is_empty());
+        time.set(20);
+        assert!(recognizer.update_pin::<B2>(true).is_empty());
+        time.set(30);
+        assert!(recognizer.update_pin::<A1>(true).is_empty());
+        time.set(40);
+        assert!(recognizer.update_pin::<B2>(false).is_empty());
+
+        time.set(300);
+        let gestures = recognizer.update(0x0002);
+        assert_eq!(gestures.get::<A1>(), Some(Click));
+        assert_eq!(gestures.get::<B2>(), Some(Click));
+        assert!(gestures.iter().map(|(pin, _)| pin).eq([1, 10]));
+    }
+}
@@ -0,0 +1,187 @@
+//! Groups of output pins written and read together.
+//!
+//! Setting pins individually takes a separate bus transaction per pin, so a
+//! parallel bus built from several pins passes through intermediate states. A
+//! [`PinGroup`] instead sets all of its pins in a single write to the output
+//! latches, spanning both banks with a sequential write if needed, and reads
+//! them back in a single transaction.
+
+use embedded_hal::digital::ErrorType as DigitalErrorType;
+use embedded_hal::i2c::I2c;
+
+use super::output::Output;
+use super::{Bank, Pin, PinId, A0};
+use crate::error::Error;
+use crate::registers::Registers;
+use crate::shared::Shared;
+
+/// A group of output pins on a single expander, written and read together.
+///
+/// Values are given as bit masks, with each bit mapping to a pin in the order
+/// the pins were given: bit 0 to the first pin, bit 1 to the second, and so
+/// on.
+///
+/// All methods may error if communication with the device fails.
+pub struct PinGroup<'a, P: Members<'a, S>, S: I2c> {
+    pins: P,
+    shared: &'a Shared<S>,
+}
+
+impl<'a, P: Members<'a, S>, S: I2c> PinGroup<'a, P, S> {
+    /// Group a tuple of output pins.
+    ///
+    /// Errors, returning the pins, if they do not all belong to the same
+    /// expander.
+    pub fn new(pins: P) -> Result<Self, P> {
+        match pins.shared() {
+            Some(private::Device(shared)) => Ok(Self { pins, shared }),
+            None => Err(pins),
+        }
+    }
+
+    /// Release the pins.
+    pub fn free(self) -> P {
+        self.pins
+    }
+
+    /// Set the levels of all pins in a single write.
+    pub fn set(&mut self, value: u16) -> Result<(), Error<S>> {
+        let (mask, bits) = spread(P::MASKS, value);
+        let olat = Registers::<A0, S>::OLAT;
+        let latched = [
+            self.shared.shadow.get(olat),
+            self.shared.shadow.get(olat + 1),
+        ];
+        let write = [
+            latched[0] & !mask[0] | bits[0],
+            latched[1] & !mask[1] | bits[1],
+        ];
+
+        let mut i2c = self.shared.i2c();
+        let address = self.shared.address;
+        match (mask[0] != 0, mask[1] != 0) {
+            (true, true) => i2c.write(address, &[olat, write[0], write[1]])?,
+            (true, false) => i2c.write(address, &[olat, write[0]])?,
+            (false, true) => i2c.write(address, &[olat + 1, write[1]])?,
+            (false, false) => {}
+        }
+        self.shared.shadow.set(olat, write[0]);
+        self.shared.shadow.set(olat + 1, write[1]);
+        Ok(())
+    }
+
+    /// Get the levels last set on all pins, without reading the device.
+    pub fn get_set(&self) -> u16 {
+        let olat = Registers::<A0, S>::OLAT;
+        gather(
+            P::MASKS,
+            [
+                self.shared.shadow.get(olat),
+                self.shared.shadow.get(olat + 1),
+            ],
+        )
+    }
+
+    /// Read the levels of all pins in a single transaction.
+    pub fn get(&mut self) -> Result<u16, Error<S>> {
+        let (mask, _) = spread(P::MASKS, 0);
+        let gpio = Registers::<A0, S>::GPIO;
+
+        let mut i2c = self.shared.i2c();
+        let address = self.shared.address;
+        let mut read = [0x00; 2];
+        match (mask[0] != 0, mask[1] != 0) {
+            (true, true) => i2c.write_read(address, &[gpio], &mut read)?,
+            (true, false) => i2c.write_read(address, &[gpio], &mut read[..1])?,
+            (false, true) => i2c.write_read(address, &[gpio + 1], &mut read[1..])?,
+            (false, false) => {}
+        }
+        Ok(gather(P::MASKS, read))
+    }
+}
+
+impl<'a, P: Members<'a, S>, S: I2c> DigitalErrorType for PinGroup<'a, P, S> {
+    type Error = Error<S>;
+}
+
+/// Map a group value onto per-bank masks and bits.
+fn spread(masks: &[(Bank, u8)], value: u16) -> ([u8; 2], [u8; 2]) {
+    let mut mask = [0x00; 2];
+    let mut bits = [0x00; 2];
+    for (index, (bank, pin_mask)) in masks.iter().enumerate() {
+        let bank = usize::from(bank.offset());
+        mask[bank] |= pin_mask;
+        if value & (1 << index) != 0 {
+            bits[bank] |= pin_mask;
+        }
+    }
+    (mask, bits)
+}
+
+/// Map per-bank register values onto a group value.
+fn gather(masks: &[(Bank, u8)], read: [u8; 2]) -> u16 {
+    masks
+        .iter()
+        .enumerate()
+        .filter(|(_, (bank, pin_mask))| read[usize::from(bank.offset())] & pin_mask != 0)
+        .fold(0, |value, (index, _)| value | (1 << index))
+}
+
+/// Pins that may be grouped together.
+///
+/// Implemented for tuples of up to sixteen output pins.
+pub trait Members<'a, S: I2c>: private::Sealed<'a, S> {}
+
+mod private {
+    use super::*;
+
+    pub trait Sealed<'a, S: I2c> {
+        /// Each pin's bank and mask, in order.
+        const MASKS: &'static [(Bank, u8)];
+
+        /// The shared state of the expander all pins belong to, if they do.
+        fn shared(&self) -> Option<Device<'a, S>>;
+    }
+
+    /// A reference to an expander's shared state.
+    pub struct Device<'a, S: I2c>(pub(crate) &'a Shared<S>);
+}
+
+macro_rules! impl_members {
+    ($($pin:ident),+) => {
+        impl<'a, S: I2c, $($pin: PinId),+> private::Sealed<'a, S>
+            for ($(Pin<'a, $pin, Output, S>,)+)
+        {
+            const MASKS: &'static [(Bank, u8)] = &[$(($pin::BANK, 1 << $pin::NUMBER)),+];
+
+            #[allow(non_snake_case)]
+            fn shared(&self) -> Option<private::Device<'a, S>> {
+                let ($($pin,)+) = self;
+                let shared = [$($pin.registers.shared()),+];
+                shared
+                    .iter()
+                    .all(|other| core::ptr::eq(*other, shared[0]))
+                    .then_some(private::Device(shared[0]))
+            }
+        }
+
+        impl<'a, S: I2c, $($pin: PinId),+> Members<'a, S> for ($(Pin<'a, $pin, Output, S>,)+) {}
+    };
+}
+
+impl_members!(P0);
+impl_members!(P0, P1);
+impl_members!(P0, P1, P2);
+impl_members!(P0, P1, P2, P3);
+impl_members!(P0, P1, P2, P3, P4);
+impl_members!(P0, P1, P2, P3, P4, P5);
+impl_members!(P0, P1, P2, P3, P4, P5, P6);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13, P14);
+impl_members!(P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13, P14, P15);
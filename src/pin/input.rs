@@ -1,4 +1,8 @@
 //! Configurations for using pins as input.
+//!
+//! When an output becomes an input, its pull up is configured before its
+//! direction, so a pulled up pin is never left floating. Its output latch is
+//! left unchanged, and is driven again if the pin later becomes an output.
 
 use core::marker::PhantomData;
 
@@ -30,9 +34,9 @@ pub struct PullUp;
 
 impl InputConfiguration for PullUp {}
 
-impl<I: PinId, C: InputConfiguration, S: I2c, const A: u8> InputPin for Pin<'_, I, Input<C>, S, A> {
+impl<I: PinId, C: InputConfiguration, S: I2c> InputPin for Pin<'_, I, Input<C>, S> {
     fn is_high(&mut self) -> Result<bool, Self::Error> {
-        Ok(unsafe { self.registers.get(Registers::<I, S, A>::GPIO)? })
+        Ok(unsafe { self.registers.get(Registers::<I, S>::GPIO)? })
     }
 
     fn is_low(&mut self) -> Result<bool, Self::Error> {
@@ -40,10 +44,10 @@ impl<I: PinId, C: InputConfiguration, S: I2c, const A: u8> InputPin for Pin<'_,
     }
 }
 
-impl<'a, I: PinId, S: I2c, const A: u8> Pin<'a, I, Input<PullUp>, S, A> {
+impl<'a, I: PinId, S: I2c> Pin<'a, I, Input<PullUp>, S> {
     /// Reconfigure the pin with the internal pull up disconnected.
-    pub fn into_floating_input(mut self) -> Result<Pin<'a, I, Input<Floating>, S, A>, Error<S>> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPPU, false)? }
+    pub fn into_floating_input(mut self) -> Result<Pin<'a, I, Input<Floating>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPPU, false)? }
         Ok(Pin {
             id: PhantomData,
             mode: PhantomData,
@@ -52,20 +56,20 @@ impl<'a, I: PinId, S: I2c, const A: u8> Pin<'a, I, Input<PullUp>, S, A> {
     }
 }
 
-impl<'a, I: PinId, S: I2c, const A: u8> TryFrom<Pin<'a, I, Input<PullUp>, S, A>>
-    for Pin<'a, I, Input<Floating>, S, A>
+impl<'a, I: PinId, S: I2c> TryFrom<Pin<'a, I, Input<PullUp>, S>>
+    for Pin<'a, I, Input<Floating>, S>
 {
     type Error = Error<S>;
 
-    fn try_from(input: Pin<'a, I, Input<PullUp>, S, A>) -> Result<Self, Self::Error> {
+    fn try_from(input: Pin<'a, I, Input<PullUp>, S>) -> Result<Self, Self::Error> {
         input.into_floating_input()
     }
 }
 
-impl<'a, I: PinId, S: I2c, const A: u8> Pin<'a, I, Input<Floating>, S, A> {
+impl<'a, I: PinId, S: I2c> Pin<'a, I, Input<Floating>, S> {
     /// Reconfigure the pin with the internal pull up connected.
-    pub fn into_pull_up_input(mut self) -> Result<Pin<'a, I, Input<PullUp>, S, A>, Error<S>> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPPU, true)? }
+    pub fn into_pull_up_input(mut self) -> Result<Pin<'a, I, Input<PullUp>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPPU, true)? }
         Ok(Pin {
             id: PhantomData,
             mode: PhantomData,
@@ -74,21 +78,21 @@ impl<'a, I: PinId, S: I2c, const A: u8> Pin<'a, I, Input<Floating>, S, A> {
     }
 }
 
-impl<'a, I: PinId, S: I2c, const A: u8> TryFrom<Pin<'a, I, Input<Floating>, S, A>>
-    for Pin<'a, I, Input<PullUp>, S, A>
+impl<'a, I: PinId, S: I2c> TryFrom<Pin<'a, I, Input<Floating>, S>>
+    for Pin<'a, I, Input<PullUp>, S>
 {
     type Error = Error<S>;
 
-    fn try_from(input: Pin<'a, I, Input<Floating>, S, A>) -> Result<Self, Self::Error> {
+    fn try_from(input: Pin<'a, I, Input<Floating>, S>) -> Result<Self, Self::Error> {
         input.into_pull_up_input()
     }
 }
 
-impl<'a, I: InputPinId, S: I2c, const A: u8> Pin<'a, I, Output, S, A> {
+impl<'a, I: InputPinId, S: I2c> Pin<'a, I, Output, S> {
     /// Reconfigure the pin as an input, with the internal pull up disconnected.
-    pub fn into_floating_input(mut self) -> Result<Pin<'a, I, Input<Floating>, S, A>, Error<S>> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPPU, false)? }
-        unsafe { self.registers.set(Registers::<I, S, A>::IODIR, true)? }
+    pub fn into_floating_input(mut self) -> Result<Pin<'a, I, Input<Floating>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPPU, false)? }
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, true)? }
         Ok(Pin {
             id: PhantomData,
             mode: PhantomData,
@@ -97,9 +101,9 @@ impl<'a, I: InputPinId, S: I2c, const A: u8> Pin<'a, I, Output, S, A> {
     }
 
     /// Reconfigure the pin as an input, with the internall pull up connected.
-    pub fn into_pull_up_input(mut self) -> Result<Pin<'a, I, Input<PullUp>, S, A>, Error<S>> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPPU, true)? }
-        unsafe { self.registers.set(Registers::<I, S, A>::IODIR, true)? }
+    pub fn into_pull_up_input(mut self) -> Result<Pin<'a, I, Input<PullUp>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPPU, true)? }
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, true)? }
         Ok(Pin {
             id: PhantomData,
             mode: PhantomData,
@@ -108,22 +112,18 @@ impl<'a, I: InputPinId, S: I2c, const A: u8> Pin<'a, I, Output, S, A> {
     }
 }
 
-impl<'a, I: InputPinId, S: I2c, const A: u8> TryFrom<Pin<'a, I, Output, S, A>>
-    for Pin<'a, I, Input<Floating>, S, A>
-{
+impl<'a, I: InputPinId, S: I2c> TryFrom<Pin<'a, I, Output, S>> for Pin<'a, I, Input<Floating>, S> {
     type Error = Error<S>;
 
-    fn try_from(input: Pin<'a, I, Output, S, A>) -> Result<Self, Self::Error> {
+    fn try_from(input: Pin<'a, I, Output, S>) -> Result<Self, Self::Error> {
         input.into_floating_input()
     }
 }
 
-impl<'a, I: InputPinId, S: I2c, const A: u8> TryFrom<Pin<'a, I, Output, S, A>>
-    for Pin<'a, I, Input<PullUp>, S, A>
-{
+impl<'a, I: InputPinId, S: I2c> TryFrom<Pin<'a, I, Output, S>> for Pin<'a, I, Input<PullUp>, S> {
     type Error = Error<S>;
 
-    fn try_from(input: Pin<'a, I, Output, S, A>) -> Result<Self, Self::Error> {
+    fn try_from(input: Pin<'a, I, Output, S>) -> Result<Self, Self::Error> {
         input.into_pull_up_input()
     }
 }
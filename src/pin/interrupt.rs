@@ -8,17 +8,19 @@
 //! minimizing timing quirks like missed edges.
 
 use core::marker::PhantomData;
-use core::sync::atomic::{AtomicU8, Ordering};
 
-use embedded_hal::digital::InputPin;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
 use embedded_hal::i2c::I2c;
-use embedded_hal_bus::i2c::{AtomicDevice, AtomicError};
 
 use super::{Bank, PinMode};
 use crate::error::Error;
 use crate::pin::input::{Input, InputConfiguration};
+use crate::pin::A0;
 use crate::pin::{Pin, PinId};
-use crate::registers::Registers;
+use crate::port::{BankId, Port, GENERAL};
+use crate::registers::{Registers, Snapshot, IOCON_SENTINEL};
+use crate::shared::Shared;
 
 /// Marker type for pins set as interrupts.
 pub struct Interrupt<C: InputConfiguration> {
@@ -27,11 +29,9 @@ pub struct Interrupt<C: InputConfiguration> {
 
 impl<C: InputConfiguration> PinMode for Interrupt<C> {}
 
-impl<I: PinId, C: InputConfiguration, S: I2c, const A: u8> InputPin
-    for Pin<'_, I, Interrupt<C>, S, A>
-{
+impl<I: PinId, C: InputConfiguration, S: I2c> InputPin for Pin<'_, I, Interrupt<C>, S> {
     fn is_high(&mut self) -> Result<bool, Self::Error> {
-        Ok(unsafe { self.registers.get(Registers::<I, S, A>::GPIO)? })
+        Ok(unsafe { self.registers.get(Registers::<I, S>::GPIO)? })
     }
 
     fn is_low(&mut self) -> Result<bool, Self::Error> {
@@ -39,26 +39,132 @@ impl<I: PinId, C: InputConfiguration, S: I2c, const A: u8> InputPin
     }
 }
 
-impl<'a, I: PinId, C: InputConfiguration, S: I2c, const A: u8> Pin<'a, I, Input<C>, S, A> {
+/// Waiting for interrupts requires the interrupt controller to be notified of
+/// them with [`InterruptController::interrupt()`], typically from an interrupt
+/// service routine for the host pin connected to the bank's interrupt output.
+/// Edges are those captured by the device when triggering an interrupt.
+///
+/// A pin sensing a level only triggers interrupts at that level, so waiting
+/// for the opposite level, or for an edge to it, errors with
+/// [`Error::Unsupported`] rather than waiting forever.
+///
+/// Waiting relies on a [`critical_section`] implementation being provided.
+#[cfg(feature = "async")]
+impl<I: PinId, C: InputConfiguration, S: I2c> embedded_hal_async::digital::Wait
+    for Pin<'_, I, Interrupt<C>, S>
+{
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_level(true).await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_level(false).await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(Some(true)).await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(Some(false)).await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(None).await
+    }
+}
+
+#[cfg(feature = "async")]
+impl<I: PinId, C: InputConfiguration, S: I2c> Pin<'_, I, Interrupt<C>, S> {
+    /// Check that interrupts on the pin can report a level.
+    ///
+    /// Errors with [`Error::Unsupported`] if the pin senses the opposite
+    /// level.
+    fn reports(&self, level: bool) -> Result<(), Error<S>> {
+        match (self.sense(), level) {
+            (Sense::High, false) | (Sense::Low, true) => Err(Error::Unsupported),
+            _ => Ok(()),
+        }
+    }
+
+    /// The signal raised by interrupts on the pin.
+    fn signal(&self) -> &crate::sync::Signal {
+        &self.registers.shared().interrupts.signals[index::<I>()]
+    }
+
+    /// Wait for the next interrupt on the pin, getting the state captured at
+    /// it.
+    async fn wait(&self) -> bool {
+        self.registers.shared().interrupts.wait(index::<I>()).await
+    }
+
+    /// Wait until the pin is at a level.
+    async fn wait_for_level(&mut self, level: bool) -> Result<(), Error<S>> {
+        self.reports(level)?;
+
+        // Discarding old interrupts before reading the level ensures a change
+        // just after the read is still seen. Interrupts pending on other pins
+        // are cleared by reading the level, so are recorded rather than lost.
+        self.signal().reset();
+        let (_, state) = self.registers.shared().sample()?;
+        let high = u16::from_le_bytes(state) & (1 << index::<I>()) != 0;
+        if high == level {
+            return Ok(());
+        }
+
+        while self.wait().await != level {}
+        Ok(())
+    }
+
+    /// Wait for an interrupt, capturing a level if given.
+    async fn wait_for_edge(&mut self, level: Option<bool>) -> Result<(), Error<S>> {
+        if let Some(level) = level {
+            self.reports(level)?;
+        }
+
+        self.signal().reset();
+        loop {
+            let captured = self.wait().await;
+            if level.is_none_or(|level| level == captured) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<'a, I: PinId, C: InputConfiguration, S: I2c> Pin<'a, I, Input<C>, S> {
     /// Reconfigure the pin to trigger interrupts.
+    ///
+    /// With [`Pending::Discard`], any interrupt already recorded for the pin,
+    /// such as one from before it was last disabled, is discarded before the
+    /// pin is enabled, so the first interrupt seen is a fresh one. Interrupts
+    /// pending on other pins of the bank are kept.
     pub fn enable_interrupt(
         mut self,
         sense: Sense,
-    ) -> Result<Pin<'a, I, Interrupt<C>, S, A>, Error<S>> {
+        pending: Pending,
+    ) -> Result<Pin<'a, I, Interrupt<C>, S>, Error<S>> {
         match sense {
             Sense::High => unsafe {
-                self.registers.set(Registers::<I, S, A>::INTCON, true)?;
-                self.registers.set(Registers::<I, S, A>::DEFVAL, false)?;
+                self.registers.set(Registers::<I, S>::INTCON, true)?;
+                self.registers.set(Registers::<I, S>::DEFVAL, false)?;
             },
             Sense::Low => unsafe {
-                self.registers.set(Registers::<I, S, A>::INTCON, true)?;
-                self.registers.set(Registers::<I, S, A>::DEFVAL, true)?;
+                self.registers.set(Registers::<I, S>::INTCON, true)?;
+                self.registers.set(Registers::<I, S>::DEFVAL, true)?;
             },
             Sense::Edge => unsafe {
-                self.registers.set(Registers::<I, S, A>::INTCON, false)?;
+                self.registers.set(Registers::<I, S>::INTCON, false)?;
             },
         }
-        unsafe { self.registers.set(Registers::<I, S, A>::GPINTEN, true)? }
+
+        // Discarding while interrupts are still disabled ensures an interrupt
+        // just after enabling them is kept.
+        if pending == Pending::Discard {
+            self.registers.shared().discard(I::BANK, 1 << I::NUMBER)?;
+        }
+
+        unsafe { self.registers.set(Registers::<I, S>::GPINTEN, true)? }
 
         Ok(Pin {
             id: PhantomData,
@@ -68,128 +174,761 @@ impl<'a, I: PinId, C: InputConfiguration, S: I2c, const A: u8> Pin<'a, I, Input<
     }
 }
 
+/// What to do with interrupts pending on a pin when enabling its interrupts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pending {
+    /// Keep pending interrupts, to be reported as usual.
+    Keep,
+    /// Discard pending interrupts.
+    Discard,
+}
+
 /// An interrupt trigger condition.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Sense {
-    /// Trigger on low level.
-    High,
     /// Trigger on high level.
+    High,
+    /// Trigger on low level.
     Low,
     /// Trigger on either edge.
     Edge,
 }
 
-impl<'a, I: PinId, C: InputConfiguration, S: I2c, const A: u8> Pin<'a, I, Interrupt<C>, S, A> {
+impl<'a, I: PinId, C: InputConfiguration, S: I2c> Pin<'a, I, Interrupt<C>, S> {
     /// Reconfigure the pin not to trigger interrupts.
-    pub fn disable_interrupt(mut self) -> Result<Pin<'a, I, Input<C>, S, A>, Error<S>> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPINTEN, false)? };
+    pub fn disable_interrupt(mut self) -> Result<Pin<'a, I, Input<C>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPINTEN, false)? };
         Ok(Pin {
             id: PhantomData,
             mode: PhantomData,
             registers: self.registers,
         })
     }
+
+    /// Change the condition that triggers interrupts.
+    ///
+    /// Interrupts stay enabled throughout: the default value and control
+    /// registers of both banks are rewritten together in a single sequential
+    /// write, so no event is missed while the condition changes.
+    pub fn set_sense(&mut self, sense: Sense) -> Result<(), Error<S>> {
+        let shared = self.registers.shared();
+        let defval_address = Registers::<A0, S>::DEFVAL;
+        let intcon_address = Registers::<A0, S>::INTCON;
+
+        let mut defval = [
+            shared.shadow.get(defval_address),
+            shared.shadow.get(defval_address + 1),
+        ];
+        let mut intcon = [
+            shared.shadow.get(intcon_address),
+            shared.shadow.get(intcon_address + 1),
+        ];
+
+        let bank = usize::from(I::BANK.offset());
+        let mask = 1 << I::NUMBER;
+
+        match sense {
+            Sense::High => {
+                intcon[bank] |= mask;
+                defval[bank] &= !mask;
+            }
+            Sense::Low => {
+                intcon[bank] |= mask;
+                defval[bank] |= mask;
+            }
+            Sense::Edge => {
+                intcon[bank] &= !mask;
+            }
+        }
+
+        shared.i2c().write(
+            shared.address,
+            &[defval_address, defval[0], defval[1], intcon[0], intcon[1]],
+        )?;
+        shared.shadow.set(defval_address, defval[0]);
+        shared.shadow.set(defval_address + 1, defval[1]);
+        shared.shadow.set(intcon_address, intcon[0]);
+        shared.shadow.set(intcon_address + 1, intcon[1]);
+        Ok(())
+    }
+
+    /// The condition that triggers interrupts.
+    pub fn sense(&self) -> Sense {
+        if !self.registers.cached(Registers::<I, S>::INTCON) {
+            Sense::Edge
+        } else if self.registers.cached(Registers::<I, S>::DEFVAL) {
+            Sense::Low
+        } else {
+            Sense::High
+        }
+    }
+
+    /// Temporarily stop the pin from triggering interrupts, keeping its
+    /// configuration.
+    pub fn mask(&mut self) -> Result<(), Error<S>> {
+        unsafe { self.registers.update(Registers::<I, S>::GPINTEN, false) }
+    }
+
+    /// Allow the pin to trigger interrupts again, after [`Self::mask()`].
+    pub fn unmask(&mut self) -> Result<(), Error<S>> {
+        unsafe { self.registers.update(Registers::<I, S>::GPINTEN, true) }
+    }
+
+    /// Whether the pin is stopped from triggering interrupts, by
+    /// [`Self::mask()`].
+    pub fn is_masked(&self) -> bool {
+        !self.registers.cached(Registers::<I, S>::GPINTEN)
+    }
+}
+
+/// Interrupt statistics for a pin, from
+/// [`InterruptController::statistics()`].
+///
+/// Counts saturate rather than wrapping.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// The number of interrupts recorded.
+    pub seen: u32,
+    /// The number of interrupts recorded while an earlier one was still
+    /// waiting to be checked with [`InterruptController::triggered()`], or
+    /// received by a task waiting on the pin, and so merged into it.
+    ///
+    /// A growing count means the pin changes faster than it is checked, and
+    /// edges are being lost.
+    pub coalesced: u32,
+    /// The most interrupts recorded for the pin between two checks.
+    pub max_depth: u32,
+}
+
+/// A handler for interrupts on a pin, registered with
+/// [`InterruptController::set_handler()`].
+///
+/// Implemented for closures taking the state at the pin's interrupt.
+pub trait Handler {
+    /// Handle an interrupt, given the state at the pin's interrupt.
+    fn handle(&mut self, state: bool);
+}
+
+impl<F: FnMut(bool)> Handler for F {
+    fn handle(&mut self, state: bool) {
+        self(state)
+    }
 }
 
 /// A centralized hub for coordinating interrupts across all pins on an
 /// expander.
-pub struct InterruptController<'a, S: I2c, const A: u8> {
-    i2c: AtomicDevice<'a, S>,
+///
+/// Generic over host pins `A` and `B` connected to the expander's interrupt
+/// outputs, which are optional and default to [`Unconnected`].
+pub struct InterruptController<'a, S: I2c, R = (), D = (), A = Unconnected, B = Unconnected> {
+    pub(crate) shared: &'a Shared<S>,
+    reset: &'a mut R,
+    delay: &'a mut D,
+
+    interrupt_a: A,
+    interrupt_b: B,
+
+    /// The state of all pins at the last poll.
+    previous: Option<[u8; 2]>,
 
-    interrupt_flag: (AtomicU8, AtomicU8),
-    interrupt_capture: (AtomicU8, AtomicU8),
+    storm: Storm,
+
+    /// Handlers for each pin, indexed by bank then number.
+    handlers: [Option<Entry<'a>>; 16],
+}
+
+/// A handler registered for a pin.
+enum Entry<'a> {
+    Function(fn(bool)),
+    Handler(&'a mut (dyn Handler + Send)),
 }
 
-impl<'a, S: I2c, const A: u8> InterruptController<'a, S, A> {
-    pub(crate) unsafe fn new(i2c: AtomicDevice<'a, S>) -> Self {
+/// State for protecting against interrupt storms from level sensitive pins.
+struct Storm {
+    enabled: bool,
+    /// Pins masked by storm protection, per bank.
+    masked: [u8; 2],
+    /// The number of times each pin was masked, indexed by bank then number.
+    counts: [u32; 16],
+}
+
+impl<'a, S: I2c, R, D> InterruptController<'a, S, R, D> {
+    pub(crate) unsafe fn new(shared: &'a Shared<S>, reset: &'a mut R, delay: &'a mut D) -> Self {
         Self {
-            i2c,
-            interrupt_flag: (AtomicU8::new(0), AtomicU8::new(0)),
-            interrupt_capture: (AtomicU8::new(0), AtomicU8::new(0)),
+            shared,
+            reset,
+            delay,
+            interrupt_a: Unconnected,
+            interrupt_b: Unconnected,
+            previous: None,
+            storm: Storm {
+                enabled: false,
+                masked: [0x00; 2],
+                counts: [0; 16],
+            },
+            handlers: [const { None }; 16],
         }
     }
 
+    /// Attach host pins connected to the device's interrupt outputs for banks
+    /// A and B, enabling [`InterruptController::service()`].
+    ///
+    /// The interrupt outputs are expected to be active low, as they are by
+    /// default. Pass [`Unconnected`] for a bank whose output is not connected.
+    pub fn with_interrupt_pins<A: InputPin, B: InputPin>(
+        self,
+        a: A,
+        b: B,
+    ) -> InterruptController<'a, S, R, D, A, B> {
+        InterruptController {
+            shared: self.shared,
+            reset: self.reset,
+            delay: self.delay,
+            interrupt_a: a,
+            interrupt_b: b,
+            previous: self.previous,
+            storm: self.storm,
+            handlers: self.handlers,
+        }
+    }
+}
+
+impl<'a, S: I2c, R, D, A, B> InterruptController<'a, S, R, D, A, B> {
+    /// Detach the host pins connected to the device's interrupt outputs,
+    /// returning them.
+    #[allow(clippy::type_complexity)]
+    pub fn without_interrupt_pins(self) -> (InterruptController<'a, S, R, D>, A, B) {
+        (
+            InterruptController {
+                shared: self.shared,
+                reset: self.reset,
+                delay: self.delay,
+                interrupt_a: Unconnected,
+                interrupt_b: Unconnected,
+                previous: self.previous,
+                storm: self.storm,
+                handlers: self.handlers,
+            },
+            self.interrupt_a,
+            self.interrupt_b,
+        )
+    }
+
     /// Handle an interrupt on a bank.
     ///
     /// Calling this method clears the interrupt condition and records the cause
     /// internally. It should be called immediately in an interrupt service
     /// routine. To check if a specific pin triggered an interrupt, use
     /// [`Self::triggered()`] at any time.
-    pub fn interrupt(&mut self, bank: Bank) -> Result<(), AtomicError<S::Error>> {
-        let intf_address = match bank {
-            #[allow(clippy::identity_op)]
-            Bank::A => Registers::<crate::pin::A0, S, A>::INTF + 0,
-            Bank::B => Registers::<crate::pin::B0, S, A>::INTF + 1,
-        };
+    pub fn interrupt(&mut self, bank: Bank) -> Result<(), Error<S>> {
+        let intf_address = Registers::<A0, S>::INTF + bank.offset();
 
         let mut intf_read = [0x00];
-        self.i2c.write_read(A, &[intf_address], &mut intf_read)?;
-
-        match bank {
-            Bank::A => self
-                .interrupt_flag
-                .0
-                .fetch_or(intf_read[0], Ordering::Relaxed),
-            Bank::B => self
-                .interrupt_flag
-                .1
-                .fetch_or(intf_read[0], Ordering::Relaxed),
-        };
-
-        let intcap_address = match bank {
-            #[allow(clippy::identity_op)]
-            Bank::A => Registers::<crate::pin::A0, S, A>::INTCAP + 0,
-            Bank::B => Registers::<crate::pin::B0, S, A>::INTCAP + 1,
-        };
+        self.shared
+            .i2c()
+            .write_read(self.shared.address, &[intf_address], &mut intf_read)?;
+
+        // Storming pins are masked before the captured state is read, as
+        // reading it clears the interrupt, which a level that persists on an
+        // unmasked pin would immediately raise again.
+        self.protect(bank, intf_read[0])?;
+
+        let intcap_address = Registers::<A0, S>::INTCAP + bank.offset();
 
         let mut intcap_read = [0x00];
-        self.i2c
-            .write_read(A, &[intcap_address], &mut intcap_read)?;
+        self.shared
+            .i2c()
+            .write_read(self.shared.address, &[intcap_address], &mut intcap_read)?;
 
-        let masked_intcap_read = intcap_read[0] & intf_read[0];
+        self.record(bank, intf_read[0], intcap_read[0]);
+        Ok(())
+    }
 
-        match bank {
-            Bank::A => {
-                let intcap = self.interrupt_capture.0.load(Ordering::Relaxed);
-                let masked_intcap = intcap & !intf_read[0];
+    /// Detect changes on both banks by polling, for devices whose interrupt
+    /// outputs are not connected.
+    ///
+    /// Calling this method reads the state of all pins in a single
+    /// transaction, and records an interrupt for each pin whose configured
+    /// [`Sense`] would have triggered one: edge sensitive pins whose state
+    /// differs from the previous poll, and level sensitive pins at their
+    /// triggering level. Interrupts are then checked with
+    /// [`Self::triggered()`], exactly as if they had been raised by the
+    /// device. It should be called periodically, and changes shorter than the
+    /// polling period may be missed.
+    ///
+    /// The first poll only records interrupts for level sensitive pins.
+    pub fn poll(&mut self) -> Result<(), Error<S>> {
+        let mut gpio_read = [0x00; 2];
+        self.shared.i2c().write_read(
+            self.shared.address,
+            &[Registers::<A0, S>::GPIO],
+            &mut gpio_read,
+        )?;
+
+        let previous = self.previous.replace(gpio_read);
+        self.rearm_idle(gpio_read)?;
 
-                let modified = masked_intcap + masked_intcap_read;
-                self.interrupt_capture.0.store(modified, Ordering::Relaxed)
+        for bank in [Bank::A, Bank::B] {
+            let offset = bank.offset();
+            let register = |base: u8| self.shared.shadow.get(base + offset);
+            let enabled = register(Registers::<A0, S>::GPINTEN);
+            let level = register(Registers::<A0, S>::INTCON);
+            let default = register(Registers::<A0, S>::DEFVAL);
+
+            let value = gpio_read[usize::from(offset)];
+            let changed = match previous {
+                Some(previous) => value ^ previous[usize::from(offset)],
+                None => 0x00,
+            };
+
+            let flags = enabled & ((level & (value ^ default)) | (!level & changed));
+            if flags != 0 {
+                self.record(bank, flags, value);
+                self.protect(bank, flags)?;
             }
-            Bank::B => {
-                let intcap = self.interrupt_capture.1.load(Ordering::Relaxed);
-                let masked_intcap = intcap & !intf_read[0];
+        }
 
-                let modified = masked_intcap + masked_intcap_read;
-                self.interrupt_capture.1.store(modified, Ordering::Relaxed)
+        Ok(())
+    }
+
+    /// Read the state of all pins in a single transaction, without losing
+    /// interrupts.
+    ///
+    /// Reading the state of pins clears interrupts pending on the device, so
+    /// the interrupt flag and capture registers are read in the same
+    /// transaction, and any interrupts found are recorded as by
+    /// [`Self::interrupt()`]. Returns the state of banks A and B.
+    ///
+    /// Errors if communication with the device fails.
+    pub fn sample(&mut self) -> Result<[u8; 2], Error<S>> {
+        let (flags, state) = self.shared.sample()?;
+
+        for bank in [Bank::A, Bank::B] {
+            let flags = flags[usize::from(bank.offset())];
+            if flags != 0 {
+                self.protect(bank, flags)?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Enable or disable protection against interrupt storms from level
+    /// sensitive pins.
+    ///
+    /// A pin sensing a level keeps triggering interrupts for as long as the
+    /// level persists, so an interrupt service routine can spin. With
+    /// protection enabled, such a pin is masked as soon as it triggers an
+    /// interrupt. It can be unmasked explicitly with [`Pin::unmask()`], or
+    /// automatically by [`Self::rearm()`] or [`Self::poll()`] once its level
+    /// has returned to idle. Protection is disabled by default.
+    pub fn set_storm_protection(&mut self, enabled: bool) {
+        self.storm.enabled = enabled;
+    }
+
+    /// The number of times a pin has been masked by storm protection.
+    pub fn storms<I: PinId>(&self) -> u32 {
+        self.storm.counts[index::<I>()]
+    }
+
+    /// Interrupt statistics for a pin, counted since the device was split or
+    /// statistics were last reset.
+    ///
+    /// Interrupts are counted as they are recorded by
+    /// [`Self::interrupt()`] or [`Self::poll()`], so the counts reflect how
+    /// often the controller is serviced as well as how often the pin
+    /// changes: the device itself latches only the first interrupt on a bank
+    /// until it is handled.
+    pub fn statistics<I: PinId>(&self) -> Statistics {
+        let (seen, coalesced, max_depth) = self.shared.interrupts.statistics(index::<I>());
+        Statistics {
+            seen,
+            coalesced,
+            max_depth,
+        }
+    }
+
+    /// Reset the interrupt statistics of all pins.
+    pub fn reset_statistics(&mut self) {
+        self.shared.interrupts.reset_statistics();
+    }
+
+    /// Unmask pins masked by storm protection whose level has returned to
+    /// idle.
+    ///
+    /// The state of pins is read as by [`Self::sample()`], so interrupts
+    /// pending on other pins are recorded rather than lost.
+    ///
+    /// Errors if communication with the device fails.
+    pub fn rearm(&mut self) -> Result<(), Error<S>> {
+        let state = self.sample()?;
+        self.rearm_idle(state)
+    }
+
+    /// Unmask pins masked by storm protection whose level in a read of both
+    /// banks is idle.
+    fn rearm_idle(&mut self, gpio_read: [u8; 2]) -> Result<(), Error<S>> {
+        for offset in 0..2 {
+            let gpinten_address = Registers::<A0, S>::GPINTEN + offset;
+            let defval_address = Registers::<A0, S>::DEFVAL + offset;
+            let gpinten = self.shared.shadow.get(gpinten_address);
+            let defval = self.shared.shadow.get(defval_address);
+
+            // Pins unmasked explicitly are no longer tracked.
+            let masked = &mut self.storm.masked[usize::from(offset)];
+            *masked &= !gpinten;
+
+            let idle = *masked & !(gpio_read[usize::from(offset)] ^ defval);
+            if idle != 0 {
+                self.shared
+                    .i2c()
+                    .write(self.shared.address, &[gpinten_address, gpinten | idle])?;
+                self.shared.shadow.set(gpinten_address, gpinten | idle);
+                *masked &= !idle;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mask level sensitive pins that triggered interrupts on a bank, if
+    /// storm protection is enabled.
+    fn protect(&mut self, bank: Bank, flags: u8) -> Result<(), Error<S>> {
+        if !self.storm.enabled {
+            return Ok(());
+        }
+
+        let offset = bank.offset();
+        let gpinten_address = Registers::<A0, S>::GPINTEN + offset;
+        let intcon_address = Registers::<A0, S>::INTCON + offset;
+        let gpinten = self.shared.shadow.get(gpinten_address);
+        let level = self.shared.shadow.get(intcon_address);
+
+        let storming = flags & level & gpinten;
+        if storming == 0 {
+            return Ok(());
+        }
+
+        self.shared
+            .i2c()
+            .write(self.shared.address, &[gpinten_address, gpinten & !storming])?;
+        self.shared.shadow.set(gpinten_address, gpinten & !storming);
+        self.storm.masked[usize::from(offset)] |= storming;
+
+        for number in 0..8 {
+            if storming & 1 << number != 0 {
+                let count = &mut self.storm.counts[usize::from(offset) * 8 + number];
+                *count = count.saturating_add(1);
             }
         }
 
         Ok(())
     }
 
+    /// Record interrupts on a bank, with the state captured at each.
+    fn record(&self, bank: Bank, flags: u8, captured: u8) {
+        self.shared.interrupts.record(bank, flags, captured);
+    }
+
     /// Check whether a pin has triggered an interrupt since the last call to
     /// this method, and if so get the state at the pin's last interrupt.
     pub fn triggered<I: PinId, C: InputConfiguration>(
         &self,
-        _pin: &Pin<'_, I, Interrupt<C>, S, A>,
+        _pin: &Pin<'_, I, Interrupt<C>, S>,
     ) -> Option<bool> {
-        let mask = 1 << I::NUMBER;
+        self.shared.interrupts.take(I::BANK, 1 << I::NUMBER)
+    }
 
-        let read = match I::BANK {
-            Bank::A => self.interrupt_flag.0.fetch_and(!mask, Ordering::Relaxed),
-            Bank::B => self.interrupt_flag.1.fetch_and(!mask, Ordering::Relaxed),
-        };
+    /// Check which pins 0 to 6 of a port have triggered interrupts since the
+    /// last call to this method or [`Self::triggered()`] for them.
+    ///
+    /// Returns a mask of the pins that triggered, and their states at their
+    /// last interrupts.
+    pub fn triggered_port<P: BankId, C: InputConfiguration>(
+        &self,
+        _port: &Port<'_, P, Interrupt<C>, S>,
+    ) -> (u8, u8) {
+        self.shared.interrupts.take_all(P::BANK, GENERAL)
+    }
+
+    /// Register a function to handle interrupts on a pin, replacing any
+    /// handler already registered for it.
+    ///
+    /// Handlers are called by [`Self::dispatch()`].
+    pub fn set_handler_fn<I: PinId, C: InputConfiguration>(
+        &mut self,
+        _pin: &Pin<'_, I, Interrupt<C>, S>,
+        handler: fn(bool),
+    ) {
+        self.handlers[index::<I>()] = Some(Entry::Function(handler));
+    }
+
+    /// Register a handler for interrupts on a pin, replacing any handler
+    /// already registered for it.
+    ///
+    /// The handler is borrowed for as long as the controller, so must be
+    /// declared before the device, or be `'static`. Handlers are called by
+    /// [`Self::dispatch()`].
+    pub fn set_handler<I: PinId, C: InputConfiguration>(
+        &mut self,
+        _pin: &Pin<'_, I, Interrupt<C>, S>,
+        handler: &'a mut (dyn Handler + Send),
+    ) {
+        self.handlers[index::<I>()] = Some(Entry::Handler(handler));
+    }
 
-        if read & mask != 0 {
-            let read = match I::BANK {
-                Bank::A => self.interrupt_capture.0.fetch_and(!mask, Ordering::Relaxed),
-                Bank::B => self.interrupt_capture.1.fetch_and(!mask, Ordering::Relaxed),
+    /// Unregister the handler for a pin.
+    pub fn clear_handler<I: PinId, C: InputConfiguration>(
+        &mut self,
+        _pin: &Pin<'_, I, Interrupt<C>, S>,
+    ) {
+        self.handlers[index::<I>()] = None;
+    }
+
+    /// Call the handlers of pins that have triggered interrupts, with the
+    /// state at each pin's last interrupt.
+    ///
+    /// Handlers are called in order of bank then pin number, from A0 to B7.
+    /// Interrupts are checked as by [`Self::triggered()`], so each is handled
+    /// once. Interrupts on pins without a handler are left to be checked.
+    ///
+    /// This does not communicate with the device, so should be called after
+    /// interrupts have been recorded, for example by [`Self::interrupt()`].
+    pub fn dispatch(&mut self) {
+        for (bank, handlers) in [Bank::A, Bank::B]
+            .into_iter()
+            .zip(self.handlers.chunks_mut(8))
+        {
+            for (number, entry) in handlers.iter_mut().enumerate() {
+                let Some(entry) = entry else {
+                    continue;
+                };
+
+                let Some(state) = self.shared.interrupts.take(bank, 1 << number) else {
+                    continue;
+                };
+
+                match entry {
+                    Entry::Function(function) => function(state),
+                    Entry::Handler(handler) => handler.handle(state),
+                }
+            }
+        }
+    }
+
+    /// Check whether the device has been reset since its configuration was
+    /// written, for example by a brown-out.
+    ///
+    /// A reset clears a sentinel bit set by the driver in the device's
+    /// configuration register, so this only requires a single read. It is
+    /// cheap enough to call periodically, or whenever an operation on a pin
+    /// fails unexpectedly. After [`DynMcp23017::attach()`], which writes
+    /// nothing, the first check instead compares the device's entire
+    /// configuration against the one read when attaching, then sets the
+    /// sentinel.
+    ///
+    /// Errors with [`Error::DeviceReset`] if the device has been reset, or if
+    /// communication with the device fails.
+    ///
+    /// [`DynMcp23017::attach()`]: crate::DynMcp23017::attach
+    pub fn check(&mut self) -> Result<(), Error<S>> {
+        let address = self.shared.address;
+        let iocon_address = Registers::<A0, S>::IOCON;
+        let expected = self.shared.shadow.load();
+        let mut i2c = self.shared.i2c();
+
+        if expected.0[usize::from(iocon_address)] & IOCON_SENTINEL != 0 {
+            let mut iocon_read = [0x00];
+            i2c.write_read(address, &[iocon_address], &mut iocon_read)?;
+            return if iocon_read[0] & IOCON_SENTINEL != 0 {
+                Ok(())
+            } else {
+                Err(Error::DeviceReset)
             };
+        }
 
-            Some(read & mask != 0)
-        } else {
-            None
+        let mut actual = Snapshot([0x00; 0x16]);
+        actual.read(&mut i2c, address)?;
+        if actual.0 != expected.0 {
+            return Err(Error::DeviceReset);
+        }
+        self.arm()
+    }
+
+    /// Check whether the device has been reset, and if so restore the
+    /// configuration of all pins.
+    ///
+    /// Returns whether the device had been reset. See [`Self::check()`] and
+    /// [`Self::restore()`].
+    ///
+    /// Errors if communication with the device fails.
+    pub fn check_and_restore(&mut self) -> Result<bool, Error<S>> {
+        match self.check() {
+            Ok(()) => Ok(false),
+            Err(Error::DeviceReset) => self.restore().map(|()| true),
+            Err(e) => Err(e),
         }
     }
+
+    /// Set the sentinel bit used to detect device resets.
+    pub(crate) fn arm(&mut self) -> Result<(), Error<S>> {
+        let iocon_address = Registers::<A0, S>::IOCON;
+        let iocon = self.shared.shadow.load().0[usize::from(iocon_address)] | IOCON_SENTINEL;
+        self.shared
+            .i2c()
+            .write(self.shared.address, &[iocon_address, iocon])?;
+        self.shared.shadow.set(iocon_address, iocon);
+        self.shared.shadow.set(iocon_address + 1, iocon);
+        Ok(())
+    }
+
+    /// Rewrite the configuration of all pins to the device.
+    ///
+    /// The configuration last written by the driver is restored in a single
+    /// sequential burst, after the output latches. This recovers pins after
+    /// the device has been reset while they were in use.
+    pub fn restore(&mut self) -> Result<(), Error<S>> {
+        let snapshot = self.shared.shadow.load();
+        snapshot.apply(&mut self.shared.i2c(), self.shared.address)?;
+        self.arm()
+    }
+}
+
+impl<S: I2c, R, D, A: InputPin, B: InputPin> InterruptController<'_, S, R, D, A, B> {
+    /// Handle interrupts on both banks until the device's interrupt outputs
+    /// are deasserted.
+    ///
+    /// This should be called from an interrupt service routine for either
+    /// host pin. Interrupt outputs are checked again after each bank is
+    /// handled, so an interrupt raised while another was being handled is not
+    /// lost.
+    ///
+    /// To keep an interrupt service routine from spinning, at most
+    /// [`SERVICE_LIMIT`] rounds are handled. Level sensitive pins keep their
+    /// interrupt outputs asserted for as long as their level persists, so
+    /// should be used with storm protection, enabled by
+    /// [`Self::set_storm_protection()`].
+    ///
+    /// Errors if reading a host pin or communication with the device fails,
+    /// or with [`Error::Storm`] if the interrupt outputs are still asserted
+    /// after the last round. Interrupts handled until then are recorded.
+    pub fn service(&mut self) -> Result<(), Error<S>> {
+        for _ in 0..SERVICE_LIMIT {
+            let a = self.interrupt_a.is_low().map_err(|_| Error::Interrupt)?;
+            let b = self.interrupt_b.is_low().map_err(|_| Error::Interrupt)?;
+
+            if !a && !b {
+                return Ok(());
+            }
+
+            if a {
+                self.interrupt(Bank::A)?;
+            }
+            if b {
+                self.interrupt(Bank::B)?;
+            }
+        }
+
+        Err(Error::Storm)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: I2c, R, D, A, B> InterruptController<'_, S, R, D, A, B>
+where
+    A: InputPin + embedded_hal_async::digital::Wait,
+    B: InputPin + embedded_hal_async::digital::Wait,
+{
+    /// Wait until either of the device's interrupt outputs is asserted, then
+    /// handle interrupts as [`Self::service()`] does.
+    ///
+    /// Calling this in a loop from a dedicated task services all interrupts,
+    /// waking tasks waiting on interrupt pins.
+    ///
+    /// Errors if reading a host pin or communication with the device fails.
+    pub async fn wait_and_service(&mut self) -> Result<(), Error<S>> {
+        use core::future::Future;
+        use core::task::Poll;
+
+        {
+            let mut a = core::pin::pin!(self.interrupt_a.wait_for_low());
+            let mut b = core::pin::pin!(self.interrupt_b.wait_for_low());
+            core::future::poll_fn(|cx| match (a.as_mut().poll(cx), b.as_mut().poll(cx)) {
+                (Poll::Ready(Err(_)), _) | (_, Poll::Ready(Err(_))) => {
+                    Poll::Ready(Err(Error::Interrupt))
+                }
+                (Poll::Pending, Poll::Pending) => Poll::Pending,
+                _ => Poll::Ready(Ok(())),
+            })
+            .await?;
+        }
+
+        self.service()
+    }
+}
+
+/// The most rounds of interrupts handled by a single call to
+/// [`InterruptController::service()`].
+pub const SERVICE_LIMIT: usize = 32;
+
+/// A placeholder for a host pin not connected to an interrupt output, which is
+/// never asserted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unconnected;
+
+impl embedded_hal::digital::ErrorType for Unconnected {
+    type Error = core::convert::Infallible;
+}
+
+impl InputPin for Unconnected {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::digital::Wait for Unconnected {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        core::future::pending().await
+    }
+}
+
+impl<S: I2c, R: OutputPin, D: DelayNs, A, B> InterruptController<'_, S, R, D, A, B> {
+    /// Reset the device by pulsing its reset pin, then restore the
+    /// configuration of all pins.
+    ///
+    /// Pins keep their types, and need not be reconfigured. Interrupts
+    /// recorded by the controller are kept, but interrupts pending on the
+    /// device are lost.
+    ///
+    /// Errors if driving the reset line or communication with the device
+    /// fails.
+    pub fn hard_reset(&mut self) -> Result<(), Error<S>> {
+        crate::hard_reset(self.shared, self.reset, self.delay)
+    }
+}
+
+/// The index of a pin, by bank then number.
+pub(crate) fn index<I: PinId>() -> usize {
+    usize::from(I::BANK.offset()) * 8 + usize::from(I::NUMBER)
 }
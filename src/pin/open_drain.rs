@@ -0,0 +1,151 @@
+//! Configurations for using pins as emulated open drain outputs.
+//!
+//! The device only has push pull outputs, so open drain behavior is emulated
+//! by holding a pin's output latch low and switching its direction: driving
+//! low when set low, and releasing the line as an input when set high. A
+//! released line is pulled high externally, or by the internal pull up if
+//! connected, and its level can be read back to observe other drivers.
+//!
+//! Only pins that may be configured as inputs can be used as open drain
+//! outputs.
+
+use core::marker::PhantomData;
+
+use embedded_hal::digital::{InputPin, OutputPin, PinState, StatefulOutputPin};
+use embedded_hal::i2c::I2c;
+
+use super::input::{Floating, Input, InputConfiguration, PullUp};
+use super::{InputPinId, Pin, PinMode};
+use crate::error::Error;
+use crate::registers::Registers;
+
+/// Marker type for pins set as emulated open drain outputs.
+pub struct OpenDrainOutput<C: InputConfiguration> {
+    config: PhantomData<C>,
+}
+
+impl<C: InputConfiguration> PinMode for OpenDrainOutput<C> {}
+
+impl<I: InputPinId, C: InputConfiguration, S: I2c> OutputPin for Pin<'_, I, OpenDrainOutput<C>, S> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, false)? };
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, true)? };
+        Ok(())
+    }
+}
+
+impl<I: InputPinId, C: InputConfiguration, S: I2c> StatefulOutputPin
+    for Pin<'_, I, OpenDrainOutput<C>, S>
+{
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(unsafe { self.registers.get(Registers::<I, S>::IODIR)? })
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_set_high().map(|x| !x)
+    }
+}
+
+impl<I: InputPinId, C: InputConfiguration, S: I2c> InputPin for Pin<'_, I, OpenDrainOutput<C>, S> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(unsafe { self.registers.get(Registers::<I, S>::GPIO)? })
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|x| !x)
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> Pin<'a, I, Input<C>, S> {
+    /// Reconfigure the pin as an emulated open drain output, keeping its pull
+    /// up configuration.
+    ///
+    /// The output latch is cleared while the pin is still an input, so the
+    /// line is released until the pin is set low.
+    pub fn into_open_drain_output(mut self) -> Result<Pin<'a, I, OpenDrainOutput<C>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::OLAT, false)? }
+        Ok(Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        })
+    }
+
+    /// Reconfigure the pin as an emulated open drain output in a given state,
+    /// keeping its pull up configuration.
+    ///
+    /// The output latch is cleared before the direction is written, so the
+    /// line is never driven high.
+    pub fn into_open_drain_output_with_state(
+        self,
+        state: PinState,
+    ) -> Result<Pin<'a, I, OpenDrainOutput<C>, S>, Error<S>> {
+        let mut pin = self.into_open_drain_output()?;
+        if state == PinState::Low {
+            pin.set_low()?;
+        }
+        Ok(pin)
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> TryFrom<Pin<'a, I, Input<C>, S>>
+    for Pin<'a, I, OpenDrainOutput<C>, S>
+{
+    type Error = Error<S>;
+
+    fn try_from(input: Pin<'a, I, Input<C>, S>) -> Result<Self, Self::Error> {
+        input.into_open_drain_output()
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> Pin<'a, I, OpenDrainOutput<C>, S> {
+    /// Reconfigure the pin as an input, with the internal pull up disconnected.
+    ///
+    /// The line is released.
+    pub fn into_floating_input(mut self) -> Result<Pin<'a, I, Input<Floating>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPPU, false)? }
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, true)? }
+        Ok(Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        })
+    }
+
+    /// Reconfigure the pin as an input, with the internal pull up connected.
+    ///
+    /// The line is released.
+    pub fn into_pull_up_input(mut self) -> Result<Pin<'a, I, Input<PullUp>, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::GPPU, true)? }
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, true)? }
+        Ok(Pin {
+            id: PhantomData,
+            mode: PhantomData,
+            registers: self.registers,
+        })
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> TryFrom<Pin<'a, I, OpenDrainOutput<C>, S>>
+    for Pin<'a, I, Input<Floating>, S>
+{
+    type Error = Error<S>;
+
+    fn try_from(output: Pin<'a, I, OpenDrainOutput<C>, S>) -> Result<Self, Self::Error> {
+        output.into_floating_input()
+    }
+}
+
+impl<'a, I: InputPinId, C: InputConfiguration, S: I2c> TryFrom<Pin<'a, I, OpenDrainOutput<C>, S>>
+    for Pin<'a, I, Input<PullUp>, S>
+{
+    type Error = Error<S>;
+
+    fn try_from(output: Pin<'a, I, OpenDrainOutput<C>, S>) -> Result<Self, Self::Error> {
+        output.into_pull_up_input()
+    }
+}
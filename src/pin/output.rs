@@ -1,8 +1,18 @@
 //! Configurations for using pins as outputs.
+//!
+//! When a pin becomes an output, it immediately drives the level held in its
+//! output latch. [`Pin::into_push_pull_output()`] leaves the latch as it is,
+//! so the pin drives whatever level was last set. To choose the level, use
+//! [`Pin::into_push_pull_output_with_state()`], which writes the latch before
+//! the direction, so the pin never drives any other level.
+//!
+//! Levels are always set through the output latch rather than the port
+//! register, so setting one pin never disturbs the latched levels of other
+//! pins in its bank.
 
 use core::marker::PhantomData;
 
-use embedded_hal::digital::{OutputPin, StatefulOutputPin};
+use embedded_hal::digital::{OutputPin, PinState, StatefulOutputPin};
 use embedded_hal::i2c::I2c;
 
 use super::input::{Input, InputConfiguration};
@@ -15,21 +25,21 @@ pub struct Output;
 
 impl PinMode for Output {}
 
-impl<I: PinId, S: I2c, const A: u8> OutputPin for Pin<'_, I, Output, S, A> {
+impl<I: PinId, S: I2c> OutputPin for Pin<'_, I, Output, S> {
     fn set_low(&mut self) -> Result<(), Self::Error> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPIO, false)? };
+        unsafe { self.registers.set(Registers::<I, S>::OLAT, false)? };
         Ok(())
     }
 
     fn set_high(&mut self) -> Result<(), Self::Error> {
-        unsafe { self.registers.set(Registers::<I, S, A>::GPIO, true)? };
+        unsafe { self.registers.set(Registers::<I, S>::OLAT, true)? };
         Ok(())
     }
 }
 
-impl<I: PinId, S: I2c, const A: u8> StatefulOutputPin for Pin<'_, I, Output, S, A> {
+impl<I: PinId, S: I2c> StatefulOutputPin for Pin<'_, I, Output, S> {
     fn is_set_high(&mut self) -> Result<bool, Self::Error> {
-        Ok(unsafe { self.registers.get(Registers::<I, S, A>::GPIO)? })
+        Ok(unsafe { self.registers.get(Registers::<I, S>::OLAT)? })
     }
 
     fn is_set_low(&mut self) -> Result<bool, Self::Error> {
@@ -37,24 +47,52 @@ impl<I: PinId, S: I2c, const A: u8> StatefulOutputPin for Pin<'_, I, Output, S,
     }
 }
 
-impl<'a, I: PinId, C: InputConfiguration, S: I2c, const A: u8> Pin<'a, I, Input<C>, S, A> {
+impl<'a, I: PinId, C: InputConfiguration, S: I2c> Pin<'a, I, Input<C>, S> {
     /// Reconfigure the pin as a push pull output.
-    pub fn into_push_pull_output(mut self) -> Result<Pin<'a, I, Output, S, A>, Error<S>> {
-        unsafe { self.registers.set(Registers::<I, S, A>::IODIR, false)? }
+    ///
+    /// The pin drives the level held in its output latch, which is left
+    /// unchanged.
+    pub fn into_push_pull_output(mut self) -> Result<Pin<'a, I, Output, S>, Error<S>> {
+        unsafe { self.registers.set(Registers::<I, S>::IODIR, false)? }
         Ok(Pin {
             id: PhantomData,
             mode: PhantomData,
             registers: self.registers,
         })
     }
+
+    /// Reconfigure the pin as a push pull output, driving a given level.
+    ///
+    /// The output latch is written before the direction, so the pin drives the
+    /// given level from the moment it becomes an output.
+    pub fn into_push_pull_output_with_state(
+        mut self,
+        state: PinState,
+    ) -> Result<Pin<'a, I, Output, S>, Error<S>> {
+        unsafe {
+            self.registers
+                .set(Registers::<I, S>::OLAT, state == PinState::High)?
+        }
+        self.into_push_pull_output()
+    }
 }
 
-impl<'a, I: PinId, C: InputConfiguration, S: I2c, const A: u8> TryFrom<Pin<'a, I, Input<C>, S, A>>
-    for Pin<'a, I, Output, S, A>
+impl<'a, I: PinId, C: InputConfiguration, S: I2c> TryFrom<Pin<'a, I, Input<C>, S>>
+    for Pin<'a, I, Output, S>
 {
     type Error = Error<S>;
 
-    fn try_from(input: Pin<'a, I, Input<C>, S, A>) -> Result<Self, Self::Error> {
+    fn try_from(input: Pin<'a, I, Input<C>, S>) -> Result<Self, Self::Error> {
         input.into_push_pull_output()
     }
 }
+
+impl<'a, I: PinId, C: InputConfiguration, S: I2c> TryFrom<(Pin<'a, I, Input<C>, S>, PinState)>
+    for Pin<'a, I, Output, S>
+{
+    type Error = Error<S>;
+
+    fn try_from((input, state): (Pin<'a, I, Input<C>, S>, PinState)) -> Result<Self, Self::Error> {
+        input.into_push_pull_output_with_state(state)
+    }
+}
@@ -0,0 +1,186 @@
+//! Handing pins back to the driver, to free the bus.
+//!
+//! Once all pins and the interrupt controller of a device have been released,
+//! the driver is no longer borrowed, and [`DynMcp23017::free()`] gives back
+//! the bus. Releasing is only needed to reset the device on the way out, as
+//! the borrow also ends when the pins and controller are dropped.
+//!
+//! [`DynMcp23017::free()`]: crate::DynMcp23017::free()
+
+use embedded_hal::i2c::I2c;
+
+use super::dynamic::DynPins;
+use super::interrupt::InterruptController;
+use super::{Pin, PinMode, Pins};
+use super::{A0, A1, A2, A3, A4, A5, A6, A7, B0, B1, B2, B3, B4, B5, B6, B7};
+use crate::error::Error;
+use crate::port::{PortA, PortB};
+use crate::shared::Shared;
+
+/// What to do with a device's configuration when releasing its pins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Release {
+    /// Leave the device as it is, so outputs keep driving their levels.
+    Keep,
+    /// Return every configuration register to its power-on value.
+    Reset,
+}
+
+/// Marker trait for a complete set of a device's pins, in any modes.
+///
+/// Implemented for [`Pins`], [`DynPins`], a pair of [`PortA`] and [`PortB`],
+/// and a tuple of all sixteen pins in order from A0 to B7.
+pub trait Parts<'a, S: I2c>: private::Sealed<'a, S> {}
+
+mod private {
+    use super::*;
+
+    pub trait Sealed<'a, S: I2c> {
+        /// Whether all pins belong to an expander.
+        fn belong_to(&self, device: Device<'a, S>) -> bool;
+    }
+
+    /// A reference to an expander's shared state.
+    pub struct Device<'a, S: I2c>(pub(crate) &'a Shared<S>);
+}
+
+impl<'a, S: I2c, R, D> InterruptController<'a, S, R, D> {
+    /// Take back all pins of the device along with the controller.
+    ///
+    /// With [`Release::Reset`], the device is returned to its power-on
+    /// configuration and any pending interrupt is cleared.
+    ///
+    /// Host pins connected to the device's interrupt outputs must first be
+    /// taken back with [`InterruptController::without_interrupt_pins()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pins do not belong to the same device as the controller.
+    ///
+    /// Errors if communication with the device fails.
+    pub fn release(self, pins: impl Parts<'a, S>, release: Release) -> Result<(), Error<S>> {
+        let shared = self.shared;
+        assert!(
+            pins.belong_to(private::Device(shared)),
+            "pins must belong to the same device as the interrupt controller"
+        );
+
+        match release {
+            Release::Keep => Ok(()),
+            Release::Reset => crate::reset(shared, crate::registers::Snapshot::POWER_ON),
+        }
+    }
+}
+
+impl<'a, S: I2c> Pins<'a, S> {
+    /// Take back all pins of the device along with its interrupt controller.
+    ///
+    /// See [`InterruptController::release()`].
+    pub fn release<R, D>(
+        self,
+        interrupt_controller: InterruptController<'a, S, R, D>,
+        release: Release,
+    ) -> Result<(), Error<S>> {
+        interrupt_controller.release(self, release)
+    }
+}
+
+impl<'a, S: I2c> private::Sealed<'a, S> for Pins<'a, S> {
+    fn belong_to(&self, device: private::Device<'a, S>) -> bool {
+        [
+            self.a0.registers.shared(),
+            self.a1.registers.shared(),
+            self.a2.registers.shared(),
+            self.a3.registers.shared(),
+            self.a4.registers.shared(),
+            self.a5.registers.shared(),
+            self.a6.registers.shared(),
+            self.a7.registers.shared(),
+            self.b0.registers.shared(),
+            self.b1.registers.shared(),
+            self.b2.registers.shared(),
+            self.b3.registers.shared(),
+            self.b4.registers.shared(),
+            self.b5.registers.shared(),
+            self.b6.registers.shared(),
+            self.b7.registers.shared(),
+        ]
+        .iter()
+        .all(|shared| core::ptr::eq(*shared, device.0))
+    }
+}
+
+impl<'a, S: I2c> Parts<'a, S> for Pins<'a, S> {}
+
+impl<'a, S: I2c> private::Sealed<'a, S> for DynPins<'a, S> {
+    fn belong_to(&self, device: private::Device<'a, S>) -> bool {
+        [
+            self.a0.registers.shared(),
+            self.a1.registers.shared(),
+            self.a2.registers.shared(),
+            self.a3.registers.shared(),
+            self.a4.registers.shared(),
+            self.a5.registers.shared(),
+            self.a6.registers.shared(),
+            self.a7.registers.shared(),
+            self.b0.registers.shared(),
+            self.b1.registers.shared(),
+            self.b2.registers.shared(),
+            self.b3.registers.shared(),
+            self.b4.registers.shared(),
+            self.b5.registers.shared(),
+            self.b6.registers.shared(),
+            self.b7.registers.shared(),
+        ]
+        .iter()
+        .all(|shared| core::ptr::eq(*shared, device.0))
+    }
+}
+
+impl<'a, S: I2c> Parts<'a, S> for DynPins<'a, S> {}
+
+impl<'a, S: I2c, M: PinMode, N: PinMode> private::Sealed<'a, S>
+    for (PortA<'a, M, S>, PortB<'a, N, S>)
+{
+    fn belong_to(&self, device: private::Device<'a, S>) -> bool {
+        core::ptr::eq(self.0.shared, device.0) && core::ptr::eq(self.1.shared, device.0)
+    }
+}
+
+impl<'a, S: I2c, M: PinMode, N: PinMode> Parts<'a, S> for (PortA<'a, M, S>, PortB<'a, N, S>) {}
+
+macro_rules! impl_parts {
+    ($(($pin:ident, $id:ident, $mode:ident)),+) => {
+        impl<'a, S: I2c, $($mode: PinMode),+> private::Sealed<'a, S>
+            for ($(Pin<'a, $id, $mode, S>,)+)
+        {
+            fn belong_to(&self, device: private::Device<'a, S>) -> bool {
+                let ($($pin,)+) = self;
+                [$($pin.registers.shared()),+]
+                    .iter()
+                    .all(|shared| core::ptr::eq(*shared, device.0))
+            }
+        }
+
+        impl<'a, S: I2c, $($mode: PinMode),+> Parts<'a, S> for ($(Pin<'a, $id, $mode, S>,)+) {}
+    };
+}
+
+impl_parts!(
+    (a0, A0, M0),
+    (a1, A1, M1),
+    (a2, A2, M2),
+    (a3, A3, M3),
+    (a4, A4, M4),
+    (a5, A5, M5),
+    (a6, A6, M6),
+    (a7, A7, M7),
+    (b0, B0, M8),
+    (b1, B1, M9),
+    (b2, B2, M10),
+    (b3, B3, M11),
+    (b4, B4, M12),
+    (b5, B5, M13),
+    (b6, B6, M14),
+    (b7, B7, M15)
+);
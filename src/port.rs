@@ -0,0 +1,303 @@
+//! Whole banks of pins, controlled together.
+//!
+//! A port owns all eight pins of a bank, and configures and accesses them with
+//! single writes and reads of the bank's registers. Pins 0 to 6 of a port
+//! share a mode, while pin 7 is always an output, as mandated by the
+//! datasheet. A port can be split into individual pins, and rejoined from
+//! them, as needed.
+
+use core::marker::PhantomData;
+
+use embedded_hal::i2c::I2c;
+
+use crate::error::Error;
+use crate::pin::input::{Floating, Input, InputConfiguration, PullUp};
+use crate::pin::interrupt::{Interrupt, Pending, Sense};
+use crate::pin::output::Output;
+use crate::pin::{Bank, InputPinId, Pin, PinId, PinMode};
+use crate::pin::{A0, A1, A2, A3, A4, A5, A6, A7, B0, B1, B2, B3, B4, B5, B6, B7};
+use crate::registers::Registers;
+use crate::shared::Shared;
+
+/// A bank of eight pins on an expander, controlled together.
+///
+/// Generic over the bank `B`, and the mode `M` of pins 0 to 6.
+///
+/// All methods may error if communication with the device fails.
+pub struct Port<'a, B: BankId, M: PinMode, S: I2c> {
+    bank: PhantomData<B>,
+    mode: PhantomData<M>,
+
+    pub(crate) shared: &'a Shared<S>,
+}
+
+/// Bank A, as a port.
+pub type PortA<'a, M, S> = Port<'a, BankA, M, S>;
+/// Bank B, as a port.
+pub type PortB<'a, M, S> = Port<'a, BankB, M, S>;
+
+/// Mask of the pins in a port that share its mode.
+pub(crate) const GENERAL: u8 = 0x7F;
+
+impl<'a, B: BankId, M: PinMode, S: I2c> Port<'a, B, M, S> {
+    pub(crate) unsafe fn new(shared: &'a Shared<S>) -> Self {
+        Self {
+            bank: PhantomData,
+            mode: PhantomData,
+            shared,
+        }
+    }
+
+    /// Read the levels of all pins.
+    pub fn read(&mut self) -> Result<u8, Error<S>> {
+        let mut read = [0x00];
+        self.shared.i2c().write_read(
+            self.shared.address,
+            &[Self::address(Registers::<A0, S>::GPIO)],
+            &mut read,
+        )?;
+        Ok(read[0])
+    }
+
+    /// Set the latched levels of all pins, driven by pins that are outputs.
+    pub fn write(&mut self, value: u8) -> Result<(), Error<S>> {
+        self.set(Registers::<A0, S>::OLAT, value)
+    }
+
+    /// Set which pins have their input polarity inverted.
+    ///
+    /// Inversion applies to every read of an input, including through pins
+    /// split from the port.
+    pub fn set_inverted(&mut self, mask: u8) -> Result<(), Error<S>> {
+        self.set(Registers::<A0, S>::IOPOL, mask)
+    }
+
+    /// Split the port into individual pins.
+    pub fn split(self) -> PortPins<'a, B, M, S> {
+        unsafe {
+            PortPins {
+                p0: Pin::new(self.shared),
+                p1: Pin::new(self.shared),
+                p2: Pin::new(self.shared),
+                p3: Pin::new(self.shared),
+                p4: Pin::new(self.shared),
+                p5: Pin::new(self.shared),
+                p6: Pin::new(self.shared),
+                p7: Pin::new(self.shared),
+            }
+        }
+    }
+
+    /// Write a register of the port's bank.
+    fn set(&mut self, register: u8, value: u8) -> Result<(), Error<S>> {
+        let address = Self::address(register);
+        self.shared
+            .i2c()
+            .write(self.shared.address, &[address, value])?;
+        self.shared.shadow.set(address, value);
+        Ok(())
+    }
+
+    /// Shift a register base address to the port's bank.
+    fn address(base: u8) -> u8 {
+        Registers::<B::P0, S>::address(base)
+    }
+
+    /// Reinterpret the port in another mode.
+    fn into_mode<N: PinMode>(self) -> Port<'a, B, N, S> {
+        Port {
+            bank: PhantomData,
+            mode: PhantomData,
+            shared: self.shared,
+        }
+    }
+}
+
+impl<'a, B: BankId, C: InputConfiguration, S: I2c> Port<'a, B, Input<C>, S> {
+    /// Reconfigure pins 0 to 6 as push pull outputs, driving the levels held
+    /// in their output latches.
+    pub fn into_push_pull_output(mut self) -> Result<Port<'a, B, Output, S>, Error<S>> {
+        self.set(Registers::<A0, S>::IODIR, 0x00)?;
+        Ok(self.into_mode())
+    }
+
+    /// Reconfigure pins 0 to 6 as push pull outputs, driving given levels.
+    ///
+    /// The output latches are written before the direction, so pins drive the
+    /// given levels from the moment they become outputs.
+    pub fn into_push_pull_output_with_state(
+        mut self,
+        value: u8,
+    ) -> Result<Port<'a, B, Output, S>, Error<S>> {
+        self.write(value)?;
+        self.into_push_pull_output()
+    }
+
+    /// Reconfigure pins 0 to 6 to trigger interrupts.
+    ///
+    /// See [`Pin::enable_interrupt`] for the handling of pending interrupts.
+    pub fn enable_interrupt(
+        mut self,
+        sense: Sense,
+        pending: Pending,
+    ) -> Result<Port<'a, B, Interrupt<C>, S>, Error<S>> {
+        match sense {
+            Sense::High => {
+                self.set(Registers::<A0, S>::INTCON, GENERAL)?;
+                self.set(Registers::<A0, S>::DEFVAL, 0x00)?;
+            }
+            Sense::Low => {
+                self.set(Registers::<A0, S>::INTCON, GENERAL)?;
+                self.set(Registers::<A0, S>::DEFVAL, GENERAL)?;
+            }
+            Sense::Edge => {
+                self.set(Registers::<A0, S>::INTCON, 0x00)?;
+            }
+        }
+        if pending == Pending::Discard {
+            self.shared.discard(B::BANK, GENERAL)?;
+        }
+        self.set(Registers::<A0, S>::GPINTEN, GENERAL)?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<'a, B: BankId, S: I2c> Port<'a, B, Input<PullUp>, S> {
+    /// Reconfigure pins 0 to 6 with the internal pull ups disconnected.
+    pub fn into_floating_input(mut self) -> Result<Port<'a, B, Input<Floating>, S>, Error<S>> {
+        self.set(Registers::<A0, S>::GPPU, 0x00)?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<'a, B: BankId, S: I2c> Port<'a, B, Input<Floating>, S> {
+    /// Reconfigure pins 0 to 6 with the internal pull ups connected.
+    pub fn into_pull_up_input(mut self) -> Result<Port<'a, B, Input<PullUp>, S>, Error<S>> {
+        self.set(Registers::<A0, S>::GPPU, GENERAL)?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<'a, B: BankId, S: I2c> Port<'a, B, Output, S> {
+    /// Reconfigure pins 0 to 6 as inputs, with the internal pull ups
+    /// disconnected.
+    pub fn into_floating_input(mut self) -> Result<Port<'a, B, Input<Floating>, S>, Error<S>> {
+        self.set(Registers::<A0, S>::GPPU, 0x00)?;
+        self.set(Registers::<A0, S>::IODIR, GENERAL)?;
+        Ok(self.into_mode())
+    }
+
+    /// Reconfigure pins 0 to 6 as inputs, with the internal pull ups
+    /// connected.
+    pub fn into_pull_up_input(mut self) -> Result<Port<'a, B, Input<PullUp>, S>, Error<S>> {
+        self.set(Registers::<A0, S>::GPPU, GENERAL)?;
+        self.set(Registers::<A0, S>::IODIR, GENERAL)?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<'a, B: BankId, C: InputConfiguration, S: I2c> Port<'a, B, Interrupt<C>, S> {
+    /// Reconfigure pins 0 to 6 not to trigger interrupts.
+    pub fn disable_interrupt(mut self) -> Result<Port<'a, B, Input<C>, S>, Error<S>> {
+        self.set(Registers::<A0, S>::GPINTEN, 0x00)?;
+        Ok(self.into_mode())
+    }
+}
+
+/// All pins of a port, with pins 0 to 6 in the port's mode.
+pub struct PortPins<'a, B: BankId, M: PinMode, S: I2c> {
+    pub p0: Pin<'a, B::P0, M, S>,
+    pub p1: Pin<'a, B::P1, M, S>,
+    pub p2: Pin<'a, B::P2, M, S>,
+    pub p3: Pin<'a, B::P3, M, S>,
+    pub p4: Pin<'a, B::P4, M, S>,
+    pub p5: Pin<'a, B::P5, M, S>,
+    pub p6: Pin<'a, B::P6, M, S>,
+    pub p7: Pin<'a, B::P7, Output, S>,
+}
+
+impl<'a, B: BankId, M: PinMode, S: I2c> PortPins<'a, B, M, S> {
+    /// Rejoin the pins into a port.
+    ///
+    /// Errors, returning the pins, if they do not all belong to the same
+    /// expander.
+    pub fn join(self) -> Result<Port<'a, B, M, S>, Self> {
+        let shared = [
+            self.p0.registers.shared(),
+            self.p1.registers.shared(),
+            self.p2.registers.shared(),
+            self.p3.registers.shared(),
+            self.p4.registers.shared(),
+            self.p5.registers.shared(),
+            self.p6.registers.shared(),
+            self.p7.registers.shared(),
+        ];
+        if shared.iter().all(|other| core::ptr::eq(*other, shared[0])) {
+            Ok(unsafe { Port::new(shared[0]) })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<'a, B: BankId, M: PinMode, S: I2c> TryFrom<PortPins<'a, B, M, S>> for Port<'a, B, M, S> {
+    type Error = PortPins<'a, B, M, S>;
+
+    fn try_from(pins: PortPins<'a, B, M, S>) -> Result<Self, Self::Error> {
+        pins.join()
+    }
+}
+
+/// Marker type for bank A.
+pub struct BankA;
+/// Marker type for bank B.
+pub struct BankB;
+
+/// Marker trait for a bank identifier.
+pub trait BankId {
+    /// The bank.
+    const BANK: Bank;
+
+    /// The bank's pin 0.
+    type P0: InputPinId;
+    /// The bank's pin 1.
+    type P1: InputPinId;
+    /// The bank's pin 2.
+    type P2: InputPinId;
+    /// The bank's pin 3.
+    type P3: InputPinId;
+    /// The bank's pin 4.
+    type P4: InputPinId;
+    /// The bank's pin 5.
+    type P5: InputPinId;
+    /// The bank's pin 6.
+    type P6: InputPinId;
+    /// The bank's pin 7.
+    type P7: PinId;
+}
+
+impl BankId for BankA {
+    const BANK: Bank = Bank::A;
+
+    type P0 = A0;
+    type P1 = A1;
+    type P2 = A2;
+    type P3 = A3;
+    type P4 = A4;
+    type P5 = A5;
+    type P6 = A6;
+    type P7 = A7;
+}
+
+impl BankId for BankB {
+    const BANK: Bank = Bank::B;
+
+    type P0 = B0;
+    type P1 = B1;
+    type P2 = B2;
+    type P3 = B3;
+    type P4 = B4;
+    type P5 = B5;
+    type P6 = B6;
+    type P7 = B7;
+}
@@ -0,0 +1,188 @@
+//! Detection of devices present on a bus.
+//!
+//! Devices are identified by reading their configuration register, which is
+//! mirrored at two addresses in either register layout and whose lowest bit is
+//! unimplemented. A device in the segregated layout can read identically at
+//! both paired addresses, in which case addresses it leaves unimplemented are
+//! also checked, and it is only taken to use the segregated layout if they all
+//! read as zero. Nothing is written, and no register with read side effects
+//! (such as the port or interrupt capture registers) is touched, so probing
+//! does not disturb a device already in use.
+
+use embedded_hal::i2c::{Error as I2cError, ErrorKind, I2c};
+
+use crate::error::Error;
+use crate::is_valid_address;
+
+/// Configuration register address pair when `IOCON.BANK` is clear.
+const IOCON_PAIRED: (u8, u8) = (0x0A, 0x0B);
+/// Configuration register address pair when `IOCON.BANK` is set.
+const IOCON_SEGREGATED: (u8, u8) = (0x05, 0x15);
+/// Addresses unimplemented when `IOCON.BANK` is set, besides `0x0B`.
+const UNIMPLEMENTED: core::ops::RangeInclusive<u8> = 0x0C..=0x0F;
+
+/// The contents of a device's configuration register (`IOCON`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Iocon(u8);
+
+impl Iocon {
+    /// The raw register value.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether bank registers are segregated rather than paired.
+    pub const fn bank(self) -> bool {
+        self.0 & (1 << 7) != 0
+    }
+
+    /// Whether the interrupt pins are internally connected.
+    pub const fn mirror(self) -> bool {
+        self.0 & (1 << 6) != 0
+    }
+
+    /// Whether the address pointer is prevented from incrementing.
+    pub const fn seqop(self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+
+    /// Whether slew rate control on SDA is disabled.
+    pub const fn disslw(self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+
+    /// Whether the hardware address pins are enabled (MCP23S17 only).
+    pub const fn haen(self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    /// Whether the interrupt pins are configured as open drain.
+    pub const fn odr(self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    /// Whether the interrupt pins are active high.
+    pub const fn intpol(self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+}
+
+/// A device detected on a bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Detected {
+    /// The device's address.
+    pub address: u8,
+    /// The device's configuration at the time of detection.
+    pub iocon: Iocon,
+}
+
+/// The result of scanning all device addresses on a bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scan {
+    devices: [Option<Iocon>; 8],
+}
+
+impl Scan {
+    /// Get the configuration of the device at an address, if one was detected.
+    pub fn get(&self, address: u8) -> Option<Iocon> {
+        if is_valid_address(address) {
+            self.devices[usize::from(address - 0x20)]
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over detected devices, in ascending order of address.
+    pub fn iter(&self) -> impl Iterator<Item = Detected> + '_ {
+        (0x20..)
+            .zip(self.devices.iter())
+            .filter_map(|(address, iocon)| iocon.map(|iocon| Detected { address, iocon }))
+    }
+}
+
+/// Check whether a device is present at an address, and if so get its
+/// configuration.
+///
+/// Errors if communication fails for any reason other than the address not
+/// being acknowledged.
+///
+/// # Panics
+///
+/// Panics if the address does not lie within `0x20..=0x27`.
+pub fn probe<S: I2c>(i2c: &mut S, address: u8) -> Result<Option<Iocon>, Error<S>> {
+    assert!(
+        is_valid_address(address),
+        "address must lie within 0x20..=0x27"
+    );
+
+    detect(i2c, address).map_err(Error::Communication)
+}
+
+/// Check every device address on a bus for a device.
+///
+/// Errors if communication fails for any reason other than an address not
+/// being acknowledged.
+pub fn scan<S: I2c>(i2c: &mut S) -> Result<Scan, Error<S>> {
+    let mut devices = [None; 8];
+    for (address, device) in (0x20..).zip(devices.iter_mut()) {
+        *device = probe(i2c, address)?;
+    }
+    Ok(Scan { devices })
+}
+
+/// Identify a device at an address by its configuration register.
+pub(crate) fn detect<S: I2c>(i2c: &mut S, address: u8) -> Result<Option<Iocon>, S::Error> {
+    let Some(first) = read(i2c, address, IOCON_PAIRED.0)? else {
+        return Ok(None);
+    };
+    let second = read(i2c, address, IOCON_PAIRED.1)?;
+    let paired = consistent(first, second).filter(|iocon| !iocon.bank());
+
+    let first = read(i2c, address, IOCON_SEGREGATED.0)?;
+    let second = read(i2c, address, IOCON_SEGREGATED.1)?;
+    let segregated = first
+        .and_then(|first| consistent(first, second))
+        .filter(|iocon| iocon.bank());
+
+    match (paired, segregated) {
+        (Some(paired), Some(segregated)) => {
+            if unimplemented(i2c, address)? {
+                Ok(Some(segregated))
+            } else {
+                Ok(Some(paired))
+            }
+        }
+        (paired, segregated) => Ok(paired.or(segregated)),
+    }
+}
+
+/// Check whether the addresses left unimplemented in the segregated layout
+/// all read as zero.
+///
+/// A segregated device whose bank A output latch is clear reads identically
+/// at both paired configuration addresses, since the second is unimplemented.
+/// In the paired layout these addresses hold the pull-up and interrupt flag
+/// registers, so any set bit rules the segregated layout out.
+fn unimplemented<S: I2c>(i2c: &mut S, address: u8) -> Result<bool, S::Error> {
+    for register in UNIMPLEMENTED {
+        if read(i2c, address, register)?.is_some_and(|value| value != 0x00) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Check that two reads both plausibly came from the configuration register.
+fn consistent(first: u8, second: Option<u8>) -> Option<Iocon> {
+    (Some(first) == second && first & 1 == 0).then_some(Iocon(first))
+}
+
+/// Read a register, or get `None` if the device does not acknowledge.
+fn read<S: I2c>(i2c: &mut S, address: u8, register: u8) -> Result<Option<u8>, S::Error> {
+    let mut read = [0x00];
+    match i2c.write_read(address, &[register], &mut read) {
+        Ok(()) => Ok(Some(read[0])),
+        Err(e) if matches!(e.kind(), ErrorKind::NoAcknowledge(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
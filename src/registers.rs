@@ -2,26 +2,27 @@ use core::marker::PhantomData;
 
 use embedded_hal::i2c::I2c;
 
-use crate::pin::{Bank, PinId};
+use crate::error::Error;
+use crate::pin::{PinId, A0};
+use crate::shared::Shared;
 
 /// A pin's control registers.
-pub(crate) struct Registers<I: PinId, S: I2c, const A: u8> {
+pub(crate) struct Registers<'a, I: PinId, S: I2c> {
     id: PhantomData<I>,
-    i2c: S,
+    shared: &'a Shared<S>,
 }
 
-impl<I: PinId, S: I2c, const A: u8> Registers<I, S, A> {
-    pub(crate) unsafe fn new(i2c: S) -> Self {
+impl<'a, I: PinId, S: I2c> Registers<'a, I, S> {
+    pub(crate) unsafe fn new(shared: &'a Shared<S>) -> Self {
         Self {
             id: PhantomData,
-            i2c,
+            shared,
         }
     }
 
     /// I/O Direction Register
     pub(crate) const IODIR: u8 = 0x00;
     /// Input Polarity Register
-    #[expect(unused)]
     pub(crate) const IOPOL: u8 = 0x02;
     /// Interrupt-on-change Control Register
     pub(crate) const GPINTEN: u8 = 0x04;
@@ -30,7 +31,6 @@ impl<I: PinId, S: I2c, const A: u8> Registers<I, S, A> {
     /// Interrupt Control Register
     pub(crate) const INTCON: u8 = 0x08;
     /// Configuration Register
-    #[expect(unused)]
     pub(crate) const IOCON: u8 = 0x0A;
     /// Pull-up Resistor Configuration Register
     pub(crate) const GPPU: u8 = 0x0C;
@@ -41,40 +41,145 @@ impl<I: PinId, S: I2c, const A: u8> Registers<I, S, A> {
     /// Port Register
     pub(crate) const GPIO: u8 = 0x12;
     /// Output Latch Register
-    #[expect(unused)]
     pub(crate) const OLAT: u8 = 0x14;
 
+    /// The shared state of the pin's device.
+    pub(crate) fn shared(&self) -> &'a Shared<S> {
+        self.shared
+    }
+
     /// Read the pin's bit in a register.
-    pub(crate) unsafe fn get(&mut self, register: u8) -> Result<bool, S::Error> {
+    pub(crate) unsafe fn get(&mut self, register: u8) -> Result<bool, Error<S>> {
         let mut read = [0x00];
-        self.i2c
-            .write_read(A, &[Self::address(register)], &mut read)?;
+        self.shared
+            .i2c()
+            .write_read(self.shared.address, &[Self::address(register)], &mut read)?;
         Ok(read[0] & (1 << I::NUMBER) != 0)
     }
 
-    /// Modify the pin's bit in a register.
-    pub(crate) unsafe fn set(&mut self, register: u8, bit: bool) -> Result<(), S::Error> {
-        let mut read = [0x00];
-        self.i2c
-            .write_read(A, &[Self::address(register)], &mut read)?;
-        self.i2c.write(
-            A,
-            &[
-                Self::address(register),
-                if bit {
-                    read[0] | (1 << I::NUMBER)
-                } else {
-                    read[0] & !(1 << I::NUMBER)
-                },
-            ],
-        )
+    /// Modify the pin's bit in a register, based on the value last written by
+    /// the driver.
+    ///
+    /// The device is not read, so after a reset its other bits are rewritten
+    /// as the driver left them rather than at their power-on values.
+    pub(crate) unsafe fn set(&mut self, register: u8, bit: bool) -> Result<(), Error<S>> {
+        let address = Self::address(register);
+        let read = self.shared.shadow.get(address);
+        let write = if bit {
+            read | (1 << I::NUMBER)
+        } else {
+            read & !(1 << I::NUMBER)
+        };
+        self.shared
+            .i2c()
+            .write(self.shared.address, &[address, write])?;
+        self.shared.shadow.set(address, write);
+        Ok(())
     }
 
-    /// Shift a register base address to the pin's bank.
-    const fn address(base: u8) -> u8 {
-        base + match I::BANK {
-            Bank::A => 0,
-            Bank::B => 1,
+    /// Get the pin's bit in a register, as last written by the driver.
+    pub(crate) fn cached(&self, register: u8) -> bool {
+        self.shared.shadow.get(Self::address(register)) & (1 << I::NUMBER) != 0
+    }
+
+    /// Modify the pin's bit in a register, as [`Self::set()`] does, unless it
+    /// is already as requested.
+    pub(crate) unsafe fn update(&mut self, register: u8, bit: bool) -> Result<(), Error<S>> {
+        let address = Self::address(register);
+        let read = self.shared.shadow.get(address);
+        let write = if bit {
+            read | (1 << I::NUMBER)
+        } else {
+            read & !(1 << I::NUMBER)
+        };
+        if write != read {
+            self.shared
+                .i2c()
+                .write(self.shared.address, &[address, write])?;
+            self.shared.shadow.set(address, write);
         }
+        Ok(())
+    }
+
+    /// Shift a register base address to the pin's bank.
+    pub(crate) const fn address(base: u8) -> u8 {
+        base + I::BANK.offset()
+    }
+}
+
+/// A configuration bit set by the driver to detect device resets.
+///
+/// `IOCON.HAEN` only has an effect on the MCP23S17, so setting it on the
+/// MCP23017 is harmless, while a reset reliably clears it.
+pub(crate) const IOCON_SENTINEL: u8 = 1 << 3;
+
+/// A copy of a device's registers, indexed by address.
+#[derive(Clone, Copy)]
+pub(crate) struct Snapshot(pub(crate) [u8; 0x16]);
+
+impl Snapshot {
+    /// The configuration of a device after power-on or reset.
+    pub(crate) const POWER_ON: Self = {
+        let mut snapshot = Self([0x00; 0x16]);
+        snapshot.0[0x00] = 0xFF;
+        snapshot.0[0x01] = 0xFF;
+        snapshot
+    };
+
+    /// The configuration in which pins are first handed out, with pins A7 and
+    /// B7 as outputs and the reset sentinel set.
+    pub(crate) const SPLIT: Self = {
+        let mut snapshot = Self::POWER_ON;
+        snapshot.0[0x00] = 0x7F;
+        snapshot.0[0x01] = 0x7F;
+        snapshot.0[0x0A] = IOCON_SENTINEL;
+        snapshot.0[0x0B] = IOCON_SENTINEL;
+        snapshot
+    };
+
+    /// Write the configuration to a device.
+    ///
+    /// The device must be using the paired register layout with sequential
+    /// addressing. All configuration registers are written in a single
+    /// sequential burst, after the output latches, so that pins becoming
+    /// outputs immediately drive their latched levels.
+    pub(crate) fn apply<S: I2c>(&self, i2c: &mut S, address: u8) -> Result<(), S::Error> {
+        let olat = usize::from(Registers::<A0, S>::OLAT);
+        i2c.write(
+            address,
+            &[Registers::<A0, S>::OLAT, self.0[olat], self.0[olat + 1]],
+        )?;
+
+        let gppu_end = usize::from(Registers::<A0, S>::GPPU) + 2;
+        let mut burst = [0x00; 0x0F];
+        burst[0] = Registers::<A0, S>::IODIR;
+        burst[1..].copy_from_slice(&self.0[..gppu_end]);
+        i2c.write(address, &burst)
+    }
+
+    /// Read the configuration from a device.
+    ///
+    /// The device must be using the paired register layout with sequential
+    /// addressing. Reading stops short of the interrupt and port registers, as
+    /// reading them clears pending interrupts.
+    pub(crate) fn read<S: I2c>(&mut self, i2c: &mut S, address: u8) -> Result<(), S::Error> {
+        let gppu_end = usize::from(Registers::<A0, S>::GPPU) + 2;
+        i2c.write_read(
+            address,
+            &[Registers::<A0, S>::IODIR],
+            &mut self.0[..gppu_end],
+        )?;
+
+        let olat = usize::from(Registers::<A0, S>::OLAT);
+        i2c.write_read(
+            address,
+            &[Registers::<A0, S>::OLAT],
+            &mut self.0[olat..olat + 2],
+        )
+    }
+
+    /// Get a pin's bit in a register.
+    pub(crate) fn get<I: PinId, S: I2c>(&self, register: u8) -> bool {
+        self.0[usize::from(Registers::<I, S>::address(register))] & (1 << I::NUMBER) != 0
     }
 }
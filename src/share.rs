@@ -0,0 +1,143 @@
+//! Sharing a bus between an expander and other devices.
+//!
+//! A driver constructed with [`DynMcp23017::new()`] owns its bus, and
+//! serializes access to it from pins and the interrupt controller itself. To
+//! share a bus with other devices, construct the driver with
+//! [`DynMcp23017::new_shared()`] instead, passing a handle to a bus shared
+//! elsewhere. The handle is duplicated for each access, leaving
+//! serialization entirely to the cell holding the bus.
+//!
+//! Handles are provided for each cell in [`embedded_hal_bus::util`] and
+//! [`embedded_hal_bus::i2c`], each accessing the bus through the matching
+//! device from [`embedded_hal_bus::i2c`]. Any other [`Copy`] handle
+//! implementing [`I2c`] may be used too.
+//!
+//! The devices from [`embedded_hal_bus::i2c`] themselves, such as
+//! [`RefCellDevice`], cannot be used as handles. Each is a single exclusive
+//! handle, accessed through `&mut self`, and cannot be copied. Pins and the
+//! interrupt controller access the bus independently, so given only one
+//! device the driver must serialize access to it, on top of the device's own
+//! locking. A device may still be passed to [`DynMcp23017::new()`] if that is
+//! acceptable. To avoid locking twice, construct the handle matching the
+//! device from the same cell instead.
+//!
+//! ```ignore
+//! let bus = RefCell::new(i2c);
+//! let mut eeprom = Eeprom::new(RefCellDevice::new(&bus));
+//! let mut device = Mcp23017::<_, 0x20>::new_shared(RefCellBus::new(&bus));
+//! ```
+//!
+//! [`DynMcp23017::new()`]: crate::DynMcp23017::new()
+//! [`DynMcp23017::new_shared()`]: crate::DynMcp23017::new_shared()
+
+use core::cell::RefCell;
+
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+#[cfg(target_has_atomic = "8")]
+use embedded_hal_bus::i2c::AtomicDevice;
+#[cfg(feature = "critical-section")]
+use embedded_hal_bus::i2c::CriticalSectionDevice;
+#[cfg(feature = "std")]
+use embedded_hal_bus::i2c::MutexDevice;
+use embedded_hal_bus::i2c::RefCellDevice;
+
+macro_rules! impl_handle {
+    ($(#[$meta:meta])* $handle:ident, $cell:ty, $device:ident) => {
+        $(#[$meta])*
+        pub struct $handle<'a, T> {
+            bus: &'a $cell,
+        }
+
+        impl<'a, T> $handle<'a, T> {
+            /// Construct a handle to a shared bus.
+            pub fn new(bus: &'a $cell) -> Self {
+                Self { bus }
+            }
+        }
+
+        impl<T> Clone for $handle<'_, T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T> Copy for $handle<'_, T> {}
+
+        impl<'a, T: I2c> ErrorType for $handle<'a, T> {
+            type Error = <$device<'a, T> as ErrorType>::Error;
+        }
+
+        impl<T: I2c> I2c for $handle<'_, T> {
+            fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+                $device::new(self.bus).read(address, read)
+            }
+
+            fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+                $device::new(self.bus).write(address, write)
+            }
+
+            fn write_read(
+                &mut self,
+                address: u8,
+                write: &[u8],
+                read: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                $device::new(self.bus).write_read(address, write, read)
+            }
+
+            fn transaction(
+                &mut self,
+                address: u8,
+                operations: &mut [Operation<'_>],
+            ) -> Result<(), Self::Error> {
+                $device::new(self.bus).transaction(address, operations)
+            }
+        }
+    };
+}
+
+impl_handle!(
+    /// A handle to a bus in a [`RefCell`], accessed through a
+    /// [`RefCellDevice`].
+    ///
+    /// As with [`RefCellDevice`], accessing the bus while it is in use, such
+    /// as from an interrupt service routine, panics.
+    RefCellBus,
+    RefCell<T>,
+    RefCellDevice
+);
+
+#[cfg(feature = "critical-section")]
+impl_handle!(
+    /// A handle to a bus in a critical section mutex, accessed through a
+    /// [`CriticalSectionDevice`].
+    ///
+    /// Available with the `critical-section` feature.
+    CriticalSectionBus,
+    critical_section::Mutex<RefCell<T>>,
+    CriticalSectionDevice
+);
+
+#[cfg(feature = "std")]
+impl_handle!(
+    /// A handle to a bus in a standard library mutex, accessed through a
+    /// [`MutexDevice`].
+    ///
+    /// Accessing the bus while it is in use blocks until it is free.
+    MutexBus,
+    std::sync::Mutex<T>,
+    MutexDevice
+);
+
+#[cfg(target_has_atomic = "8")]
+impl_handle!(
+    /// A handle to a bus in an
+    /// [`AtomicCell`](embedded_hal_bus::util::AtomicCell), accessed through an
+    /// [`AtomicDevice`](embedded_hal_bus::i2c::AtomicDevice).
+    ///
+    /// As with [`AtomicDevice`](embedded_hal_bus::i2c::AtomicDevice),
+    /// accessing the bus while it is in use errors.
+    AtomicBus,
+    embedded_hal_bus::util::AtomicCell<T>,
+    AtomicDevice
+);
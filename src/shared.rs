@@ -0,0 +1,358 @@
+use core::cell::UnsafeCell;
+
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+
+use crate::error::Error;
+use crate::pin::{Bank, A0};
+use crate::registers::{Registers, Snapshot};
+#[cfg(feature = "async")]
+use crate::sync::Signal;
+use crate::sync::{Byte, Count, Flag};
+
+/// State shared between all pins and the interrupt controller of a device.
+pub(crate) struct Shared<S: I2c> {
+    cell: Cell<S>,
+    pub(crate) address: u8,
+    pub(crate) shadow: Shadow,
+    pub(crate) interrupts: Interrupts,
+}
+
+impl<S: I2c> Shared<S> {
+    pub(crate) fn new(i2c: S, address: u8) -> Self {
+        Self {
+            cell: Cell {
+                bus: UnsafeCell::new(i2c),
+                busy: Flag::new(false),
+                duplicate: None,
+            },
+            address,
+            shadow: Shadow::new(Snapshot::POWER_ON),
+            interrupts: Interrupts::new(),
+        }
+    }
+
+    /// Construct shared state around a handle to a bus shared elsewhere.
+    pub(crate) fn new_shared(i2c: S, address: u8) -> Self
+    where
+        S: Copy,
+    {
+        Self {
+            cell: Cell {
+                bus: UnsafeCell::new(i2c),
+                busy: Flag::new(false),
+                duplicate: Some(|i2c| *i2c),
+            },
+            address,
+            shadow: Shadow::new(Snapshot::POWER_ON),
+            interrupts: Interrupts::new(),
+        }
+    }
+
+    /// Get a handle to the bus.
+    pub(crate) fn i2c(&self) -> Bus<'_, S> {
+        Bus { cell: &self.cell }
+    }
+
+    /// Read the state of all pins in a single transaction, recording any
+    /// interrupts pending on the device, as reading the state clears them.
+    ///
+    /// Returns the interrupt flags and the state of banks A and B.
+    pub(crate) fn sample(&self) -> Result<([u8; 2], [u8; 2]), Error<S>> {
+        // INTF, INTCAP and GPIO for both banks, in sequence.
+        let mut read = [0x00; 6];
+        self.i2c()
+            .write_read(self.address, &[Registers::<A0, S>::INTF], &mut read)?;
+
+        for bank in [Bank::A, Bank::B] {
+            let offset = usize::from(bank.offset());
+            if read[offset] != 0 {
+                self.interrupts.record(bank, read[offset], read[2 + offset]);
+            }
+        }
+
+        Ok(([read[0], read[1]], [read[4], read[5]]))
+    }
+
+    /// Discard interrupts pending on pins of a bank.
+    ///
+    /// Pending interrupts are cleared on the device, and those on other pins
+    /// are recorded rather than lost.
+    pub(crate) fn discard(&self, bank: Bank, mask: u8) -> Result<(), Error<S>> {
+        // INTF and INTCAP for both banks, in sequence.
+        let mut read = [0x00; 4];
+        self.i2c()
+            .write_read(self.address, &[Registers::<A0, S>::INTF], &mut read)?;
+
+        for other in [Bank::A, Bank::B] {
+            let offset = usize::from(other.offset());
+            let kept = if other == bank { !mask } else { 0xFF };
+            self.interrupts
+                .record(other, read[offset] & kept, read[2 + offset]);
+        }
+        self.interrupts.discard(bank, mask);
+        Ok(())
+    }
+
+    /// Take back the bus.
+    pub(crate) fn into_inner(self) -> S {
+        self.cell.bus.into_inner()
+    }
+}
+
+/// A bus, shared between handles by refusing concurrent access.
+///
+/// Equivalent to `embedded_hal_bus::util::AtomicCell`, but able to give the
+/// bus back, and built on [`sync`](crate::sync) primitives. Handles to a bus
+/// shared elsewhere are instead duplicated for each access, without locking.
+struct Cell<S> {
+    bus: UnsafeCell<S>,
+    busy: Flag,
+    /// Copies the bus, if it is a handle that is [`Copy`].
+    duplicate: Option<fn(&S) -> S>,
+}
+
+unsafe impl<S: Send> Send for Cell<S> {}
+unsafe impl<S: Send> Sync for Cell<S> {}
+
+/// A handle to a shared bus.
+///
+/// Unless the bus is a duplicated handle, errors with [`Error::Busy`] if the
+/// bus is in use through another handle.
+pub(crate) struct Bus<'a, S> {
+    cell: &'a Cell<S>,
+}
+
+impl<S: I2c> Bus<'_, S> {
+    fn lock<T>(&mut self, f: impl FnOnce(&mut S) -> Result<T, S::Error>) -> Result<T, Error<S>> {
+        if let Some(duplicate) = self.cell.duplicate {
+            // The bus is never borrowed mutably in place, so copying it from
+            // several threads is equivalent to sending copies to them.
+            let mut bus = duplicate(unsafe { &*self.cell.bus.get() });
+            return f(&mut bus).map_err(Error::Communication);
+        }
+
+        if self.cell.busy.set() {
+            return Err(Error::Busy);
+        }
+
+        let result = f(unsafe { &mut *self.cell.bus.get() });
+
+        self.cell.busy.clear();
+
+        result.map_err(Error::Communication)
+    }
+}
+
+impl<S: I2c> ErrorType for Bus<'_, S> {
+    type Error = Error<S>;
+}
+
+impl<S: I2c> I2c for Bus<'_, S> {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.read(address, read))
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.write(address, write))
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.write_read(address, write, read))
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.transaction(address, operations))
+    }
+}
+
+/// Interrupts recorded on a device and not yet checked.
+pub(crate) struct Interrupts {
+    /// Pins that triggered interrupts, per bank.
+    flag: [Byte; 2],
+    /// The state captured at each pin's last interrupt, per bank.
+    capture: [Byte; 2],
+    /// Statistics for each pin, indexed by bank then number.
+    statistics: [Counters; 16],
+    /// Interrupts on each pin, indexed by bank then number, for tasks waiting
+    /// on them.
+    #[cfg(feature = "async")]
+    pub(crate) signals: [Signal; 16],
+}
+
+/// Interrupt counts for a pin.
+struct Counters {
+    seen: Count,
+    coalesced: Count,
+    max_depth: Count,
+    /// Interrupts recorded since the pin was last checked.
+    depth: Count,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            seen: Count::new(0),
+            coalesced: Count::new(0),
+            max_depth: Count::new(0),
+            depth: Count::new(0),
+        }
+    }
+}
+
+impl Interrupts {
+    const fn new() -> Self {
+        Self {
+            flag: [Byte::new(0), Byte::new(0)],
+            capture: [Byte::new(0), Byte::new(0)],
+            statistics: [const { Counters::new() }; 16],
+            #[cfg(feature = "async")]
+            signals: [const { Signal::new() }; 16],
+        }
+    }
+
+    /// Record interrupts on a bank, with the state captured at each.
+    pub(crate) fn record(&self, bank: Bank, flags: u8, captured: u8) {
+        let (flag, capture) = self.bank(bank);
+        flag.fetch_or(flags);
+        capture.store((capture.load() & !flags) | (captured & flags));
+
+        for number in 0..8 {
+            if flags & 1 << number != 0 {
+                let index = usize::from(bank.offset()) * 8 + number;
+
+                let counters = &self.statistics[index];
+                counters.seen.increment();
+                let depth = counters.depth.increment();
+                if depth > 1 {
+                    counters.coalesced.increment();
+                }
+                counters.max_depth.fetch_max(depth);
+
+                #[cfg(feature = "async")]
+                self.signals[index].raise(captured & 1 << number != 0);
+            }
+        }
+    }
+
+    /// Take the interrupt recorded on a pin, if any, getting the state
+    /// captured at it.
+    pub(crate) fn take(&self, bank: Bank, mask: u8) -> Option<bool> {
+        let (flags, captured) = self.take_all(bank, mask);
+        (flags != 0).then_some(captured != 0)
+    }
+
+    /// Take the interrupts recorded on pins of a bank, getting the pins that
+    /// triggered and the states captured at them.
+    pub(crate) fn take_all(&self, bank: Bank, mask: u8) -> (u8, u8) {
+        let (flag, capture) = self.bank(bank);
+        self.clear_depth(bank, mask);
+        let flags = flag.fetch_and(!mask) & mask;
+        let captured = capture.fetch_and(!flags) & flags;
+        (flags, captured)
+    }
+
+    /// Wait for the next interrupt on a pin, getting the state captured at it.
+    ///
+    /// The interrupt is consumed on receipt, as by [`Self::take()`], so later
+    /// ones are not counted as coalesced with it.
+    #[cfg(feature = "async")]
+    pub(crate) async fn wait(&self, index: usize) -> bool {
+        let captured = self.signals[index].wait().await;
+        self.statistics[index].depth.store(0);
+        captured
+    }
+
+    /// Forget interrupts recorded on pins of a bank.
+    pub(crate) fn discard(&self, bank: Bank, mask: u8) {
+        let (flag, capture) = self.bank(bank);
+        flag.fetch_and(!mask);
+        capture.fetch_and(!mask);
+        self.clear_depth(bank, mask);
+
+        #[cfg(feature = "async")]
+        for number in 0..8 {
+            if mask & 1 << number != 0 {
+                self.signals[usize::from(bank.offset()) * 8 + number].reset();
+            }
+        }
+    }
+
+    /// Get a pin's interrupt counts: interrupts seen, interrupts coalesced
+    /// with one not yet checked, and the most interrupts recorded between
+    /// checks.
+    pub(crate) fn statistics(&self, index: usize) -> (u32, u32, u32) {
+        let counters = &self.statistics[index];
+        (
+            counters.seen.load(),
+            counters.coalesced.load(),
+            counters.max_depth.load(),
+        )
+    }
+
+    /// Reset the interrupt counts of all pins.
+    pub(crate) fn reset_statistics(&self) {
+        for counters in &self.statistics {
+            counters.seen.store(0);
+            counters.coalesced.store(0);
+            counters.max_depth.store(0);
+        }
+    }
+
+    fn clear_depth(&self, bank: Bank, mask: u8) {
+        for number in 0..8 {
+            if mask & 1 << number != 0 {
+                let index = usize::from(bank.offset()) * 8 + number;
+                self.statistics[index].depth.store(0);
+            }
+        }
+    }
+
+    fn bank(&self, bank: Bank) -> (&Byte, &Byte) {
+        let offset = usize::from(bank.offset());
+        (&self.flag[offset], &self.capture[offset])
+    }
+}
+
+/// The last configuration written to a device, indexed by register address.
+pub(crate) struct Shadow([Byte; 0x16]);
+
+impl Shadow {
+    pub(crate) fn new(snapshot: Snapshot) -> Self {
+        Self(snapshot.0.map(Byte::new))
+    }
+
+    /// Get a copy of the configuration.
+    pub(crate) fn load(&self) -> Snapshot {
+        let mut snapshot = Snapshot([0x00; 0x16]);
+        for (value, register) in snapshot.0.iter_mut().zip(&self.0) {
+            *value = register.load();
+        }
+        snapshot
+    }
+
+    /// Replace the configuration.
+    pub(crate) fn store(&self, snapshot: Snapshot) {
+        for (value, register) in snapshot.0.iter().zip(&self.0) {
+            register.store(*value);
+        }
+    }
+
+    /// Get a register's value.
+    pub(crate) fn get(&self, address: u8) -> u8 {
+        self.0[usize::from(address)].load()
+    }
+
+    /// Record a register's value.
+    pub(crate) fn set(&self, address: u8, value: u8) {
+        self.0[usize::from(address)].store(value);
+    }
+}
@@ -0,0 +1,63 @@
+//! Storage for drivers that live for the rest of the program.
+//!
+//! Pins and interrupt controllers borrow the driver they were split from. To
+//! move them into tasks or threads, which require `'static` values, place the
+//! driver in a [`Storage`] first. Splitting the resulting `&'static mut`
+//! driver yields `'static` pins and an interrupt controller.
+//!
+//! Pins and interrupt controllers are [`Send`] whenever the bus (and any
+//! reset line and delay) is. Each bus access from one of them is exclusive:
+//! an access attempted while another is in progress, such as from another
+//! thread or an interrupt service routine, errors with [`Error::Busy`] rather
+//! than blocking, and may be retried.
+//!
+//! ```ignore
+//! static DEVICE: Storage<Mcp23017<I2c, 0x20>> = Storage::new();
+//!
+//! let device = DEVICE.init(Mcp23017::new(i2c));
+//! let (pins, interrupt_controller) = device.split()?;
+//! ```
+//!
+//! [`Error::Busy`]: crate::error::Error::Busy
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+use crate::sync::Flag;
+
+/// Statically allocated storage for a value, initialized at runtime.
+pub struct Storage<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    taken: Flag,
+}
+
+unsafe impl<T: Send> Sync for Storage<T> {}
+
+impl<T> Storage<T> {
+    /// Construct empty storage.
+    pub const fn new() -> Self {
+        Self {
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            taken: Flag::new(false),
+        }
+    }
+
+    /// Store a value, returning a `'static` reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value has already been stored.
+    #[allow(clippy::mut_from_ref)]
+    pub fn init(&'static self, value: T) -> &'static mut T {
+        assert!(!self.taken.set(), "storage must only be initialized once");
+
+        // Only the first caller reaches here, so the reference is unique.
+        unsafe { (*self.value.get()).write(value) }
+    }
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
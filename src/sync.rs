@@ -0,0 +1,246 @@
+//! Primitives for state shared between pins and the interrupt controller.
+//!
+//! By default these are built on atomics, which require compare-and-swap
+//! support from the target. With the `critical-section` feature, they are
+//! instead built on [`critical_section`], for targets without it.
+
+#[cfg(not(feature = "critical-section"))]
+pub(crate) use atomic::{Byte, Count, Flag};
+#[cfg(feature = "critical-section")]
+pub(crate) use critical::{Byte, Count, Flag};
+
+#[cfg(not(feature = "critical-section"))]
+mod atomic {
+    use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+    /// A boolean flag.
+    pub(crate) struct Flag(AtomicBool);
+
+    impl Flag {
+        pub(crate) const fn new(value: bool) -> Self {
+            Self(AtomicBool::new(value))
+        }
+
+        /// Set the flag, returning whether it was already set.
+        pub(crate) fn set(&self) -> bool {
+            self.0.swap(true, Ordering::Acquire)
+        }
+
+        /// Clear the flag.
+        pub(crate) fn clear(&self) {
+            self.0.store(false, Ordering::Release);
+        }
+    }
+
+    /// A byte.
+    pub(crate) struct Byte(AtomicU8);
+
+    impl Byte {
+        pub(crate) const fn new(value: u8) -> Self {
+            Self(AtomicU8::new(value))
+        }
+
+        pub(crate) fn load(&self) -> u8 {
+            self.0.load(Ordering::Relaxed)
+        }
+
+        pub(crate) fn store(&self, value: u8) {
+            self.0.store(value, Ordering::Relaxed);
+        }
+
+        /// Set bits, returning the previous value.
+        pub(crate) fn fetch_or(&self, value: u8) -> u8 {
+            self.0.fetch_or(value, Ordering::Relaxed)
+        }
+
+        /// Clear bits not in a mask, returning the previous value.
+        pub(crate) fn fetch_and(&self, value: u8) -> u8 {
+            self.0.fetch_and(value, Ordering::Relaxed)
+        }
+    }
+
+    /// A counter.
+    pub(crate) struct Count(AtomicU32);
+
+    impl Count {
+        pub(crate) const fn new(value: u32) -> Self {
+            Self(AtomicU32::new(value))
+        }
+
+        pub(crate) fn load(&self) -> u32 {
+            self.0.load(Ordering::Relaxed)
+        }
+
+        pub(crate) fn store(&self, value: u32) {
+            self.0.store(value, Ordering::Relaxed);
+        }
+
+        /// Add one, saturating, returning the new value.
+        pub(crate) fn increment(&self) -> u32 {
+            let previous = self
+                .0
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    Some(count.saturating_add(1))
+                })
+                .unwrap_or_else(|count| count);
+            previous.saturating_add(1)
+        }
+
+        /// Raise to at least a value.
+        pub(crate) fn fetch_max(&self, value: u32) {
+            self.0.fetch_max(value, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(feature = "critical-section")]
+mod critical {
+    use core::cell::Cell;
+
+    use critical_section::Mutex;
+
+    /// A boolean flag.
+    pub(crate) struct Flag(Mutex<Cell<bool>>);
+
+    impl Flag {
+        pub(crate) const fn new(value: bool) -> Self {
+            Self(Mutex::new(Cell::new(value)))
+        }
+
+        /// Set the flag, returning whether it was already set.
+        pub(crate) fn set(&self) -> bool {
+            critical_section::with(|cs| self.0.borrow(cs).replace(true))
+        }
+
+        /// Clear the flag.
+        pub(crate) fn clear(&self) {
+            critical_section::with(|cs| self.0.borrow(cs).set(false));
+        }
+    }
+
+    /// A byte.
+    pub(crate) struct Byte(Mutex<Cell<u8>>);
+
+    impl Byte {
+        pub(crate) const fn new(value: u8) -> Self {
+            Self(Mutex::new(Cell::new(value)))
+        }
+
+        pub(crate) fn load(&self) -> u8 {
+            critical_section::with(|cs| self.0.borrow(cs).get())
+        }
+
+        pub(crate) fn store(&self, value: u8) {
+            critical_section::with(|cs| self.0.borrow(cs).set(value));
+        }
+
+        /// Set bits, returning the previous value.
+        pub(crate) fn fetch_or(&self, value: u8) -> u8 {
+            critical_section::with(|cs| {
+                let cell = self.0.borrow(cs);
+                cell.replace(cell.get() | value)
+            })
+        }
+
+        /// Clear bits not in a mask, returning the previous value.
+        pub(crate) fn fetch_and(&self, value: u8) -> u8 {
+            critical_section::with(|cs| {
+                let cell = self.0.borrow(cs);
+                cell.replace(cell.get() & value)
+            })
+        }
+    }
+
+    /// A counter.
+    pub(crate) struct Count(Mutex<Cell<u32>>);
+
+    impl Count {
+        pub(crate) const fn new(value: u32) -> Self {
+            Self(Mutex::new(Cell::new(value)))
+        }
+
+        pub(crate) fn load(&self) -> u32 {
+            critical_section::with(|cs| self.0.borrow(cs).get())
+        }
+
+        pub(crate) fn store(&self, value: u32) {
+            critical_section::with(|cs| self.0.borrow(cs).set(value));
+        }
+
+        /// Add one, saturating, returning the new value.
+        pub(crate) fn increment(&self) -> u32 {
+            critical_section::with(|cs| {
+                let cell = self.0.borrow(cs);
+                cell.set(cell.get().saturating_add(1));
+                cell.get()
+            })
+        }
+
+        /// Raise to at least a value.
+        pub(crate) fn fetch_max(&self, value: u32) {
+            critical_section::with(|cs| {
+                let cell = self.0.borrow(cs);
+                cell.set(cell.get().max(value));
+            });
+        }
+    }
+}
+
+/// An event signalled to a single waiting task.
+#[cfg(feature = "async")]
+pub(crate) struct Signal(critical_section::Mutex<core::cell::RefCell<SignalState>>);
+
+#[cfg(feature = "async")]
+struct SignalState {
+    waker: Option<core::task::Waker>,
+    event: Option<bool>,
+}
+
+#[cfg(feature = "async")]
+impl Signal {
+    pub(crate) const fn new() -> Self {
+        Self(critical_section::Mutex::new(core::cell::RefCell::new(
+            SignalState {
+                waker: None,
+                event: None,
+            },
+        )))
+    }
+
+    /// Discard any event not yet waited for.
+    pub(crate) fn reset(&self) {
+        critical_section::with(|cs| self.0.borrow_ref_mut(cs).event = None);
+    }
+
+    /// Record an event, waking the waiting task.
+    ///
+    /// An event not yet waited for is replaced.
+    pub(crate) fn raise(&self, value: bool) {
+        let waker = critical_section::with(|cs| {
+            let mut state = self.0.borrow_ref_mut(cs);
+            state.event = Some(value);
+            state.waker.take()
+        });
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wait for the next event.
+    pub(crate) async fn wait(&self) -> bool {
+        core::future::poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut state = self.0.borrow_ref_mut(cs);
+                match state.event.take() {
+                    Some(value) => core::task::Poll::Ready(value),
+                    None => {
+                        state.waker = Some(cx.waker().clone());
+                        core::task::Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+}
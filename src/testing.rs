@@ -0,0 +1,38 @@
+//! Fixtures shared by unit tests.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+
+use crate::pin::debounce::Clock;
+use crate::pin::input::{Floating, Input};
+use crate::pin::{Pin, PinId};
+use crate::shared::Shared;
+
+/// A bus on which every transaction succeeds without effect.
+pub(crate) struct NoBus;
+
+impl ErrorType for NoBus {
+    type Error = Infallible;
+}
+
+impl I2c for NoBus {
+    fn transaction(&mut self, _: u8, _: &mut [Operation<'_>]) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// A clock advanced by hand.
+pub(crate) struct Manual<'a>(pub(crate) &'a Cell<u32>);
+
+impl Clock for Manual<'_> {
+    fn now(&mut self) -> u32 {
+        self.0.get()
+    }
+}
+
+/// Conjure a pin of a device on a bus without effect.
+pub(crate) fn pin<I: PinId>(shared: &Shared<NoBus>) -> Pin<'_, I, Input<Floating>, NoBus> {
+    unsafe { Pin::new(shared) }
+}
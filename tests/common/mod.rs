@@ -0,0 +1,297 @@
+//! Simulated expanders on a simulated bus.
+//!
+//! Each device models the registers of an MCP23017 in either register layout,
+//! including the address pointer, interrupt latching on change or level, and
+//! the side effects of reading the port and interrupt capture registers.
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, InputPin, OutputPin};
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+/// Register addresses in the paired layout.
+pub const IODIR: usize = 0x00;
+pub const IPOL: usize = 0x02;
+pub const GPINTEN: usize = 0x04;
+pub const DEFVAL: usize = 0x06;
+pub const INTCON: usize = 0x08;
+pub const IOCON: usize = 0x0A;
+pub const GPPU: usize = 0x0C;
+pub const INTF: usize = 0x0E;
+pub const INTCAP: usize = 0x10;
+pub const GPIO: usize = 0x12;
+pub const OLAT: usize = 0x14;
+
+const IOCON_BANK: u8 = 1 << 7;
+const IOCON_SEQOP: u8 = 1 << 5;
+
+/// A bus shared by simulated devices.
+#[derive(Clone, Default)]
+pub struct SimulatedBus {
+    devices: Arc<Mutex<Vec<Device>>>,
+}
+
+/// A simulated device.
+pub struct Device {
+    address: u8,
+    /// Registers, indexed by their address in the paired layout.
+    pub registers: [u8; 0x16],
+    pointer: u8,
+    /// Levels applied to each bank by external circuitry.
+    inputs: [u8; 2],
+    /// Every register access, as the paired layout address and whether it was
+    /// a read.
+    pub accesses: Vec<(usize, bool)>,
+}
+
+impl SimulatedBus {
+    /// Construct a bus with devices at power-on at each address.
+    pub fn new(addresses: &[u8]) -> Self {
+        let bus = Self::default();
+        for &address in addresses {
+            bus.devices.lock().unwrap().push(Device::new(address));
+        }
+        bus
+    }
+
+    /// Access the device at an address.
+    pub fn device<T>(&self, address: u8, f: impl FnOnce(&mut Device) -> T) -> T {
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices
+            .iter_mut()
+            .find(|device| device.address == address)
+            .expect("no device at address");
+        f(device)
+    }
+}
+
+/// A reset line connected to a simulated device, which resets it while low.
+pub struct ResetPin {
+    pub bus: SimulatedBus,
+    pub address: u8,
+}
+
+/// A host pin connected to one of a simulated device's active low interrupt
+/// outputs.
+pub struct InterruptPin {
+    pub bus: SimulatedBus,
+    pub address: u8,
+    pub bank: usize,
+}
+
+/// A delay that returns immediately, since simulated devices respond at once.
+pub struct NoDelay;
+
+impl Device {
+    fn new(address: u8) -> Self {
+        let mut device = Self {
+            address,
+            registers: [0x00; 0x16],
+            pointer: 0,
+            inputs: [0x00; 2],
+            accesses: Vec::new(),
+        };
+        device.reset();
+        device
+    }
+
+    /// Return to the power-on configuration, keeping external levels.
+    pub fn reset(&mut self) {
+        self.registers = [0x00; 0x16];
+        self.registers[IODIR] = 0xFF;
+        self.registers[IODIR + 1] = 0xFF;
+        self.pointer = 0;
+    }
+
+    /// Switch to the banked register layout, as if configured by other
+    /// software.
+    pub fn set_banked(&mut self) {
+        self.registers[IOCON] |= IOCON_BANK;
+        self.registers[IOCON + 1] |= IOCON_BANK;
+    }
+
+    /// Apply external levels to a bank, latching interrupts they trigger.
+    pub fn drive(&mut self, bank: usize, levels: u8) {
+        let previous = self.levels(bank);
+        self.inputs[bank] = levels;
+        let current = self.levels(bank);
+
+        let enabled = self.registers[GPINTEN + bank] & self.registers[IODIR + bank];
+        let level = self.registers[INTCON + bank];
+        let triggered = enabled
+            & ((level & (current ^ self.registers[DEFVAL + bank]))
+                | (!level & (current ^ previous)));
+        self.latch(bank, triggered);
+    }
+
+    /// Whether a bank's interrupt output is asserted.
+    pub fn interrupt(&self, bank: usize) -> bool {
+        self.registers[INTF + bank] != 0
+    }
+
+    /// The levels of a bank's pins, before polarity inversion.
+    fn levels(&self, bank: usize) -> u8 {
+        let inputs = self.registers[IODIR + bank];
+        (self.inputs[bank] & inputs) | (self.registers[OLAT + bank] & !inputs)
+    }
+
+    /// Latch an interrupt on pins of a bank, unless one is already pending.
+    fn latch(&mut self, bank: usize, pins: u8) {
+        if pins != 0 && self.registers[INTF + bank] == 0 {
+            self.registers[INTF + bank] = pins;
+            self.registers[INTCAP + bank] = self.port(bank);
+        }
+    }
+
+    /// Latch interrupts on level sensitive pins still at their triggering
+    /// level, as the device does as soon as a pending interrupt is cleared.
+    fn relatch(&mut self) {
+        for bank in 0..2 {
+            let enabled = self.registers[GPINTEN + bank] & self.registers[IODIR + bank];
+            let level = self.registers[INTCON + bank];
+            let persisting = enabled & level & (self.levels(bank) ^ self.registers[DEFVAL + bank]);
+            self.latch(bank, persisting);
+        }
+    }
+
+    /// The value of a bank's port register.
+    fn port(&self, bank: usize) -> u8 {
+        self.levels(bank) ^ (self.registers[IPOL + bank] & self.registers[IODIR + bank])
+    }
+
+    /// Map an address to a register in the paired layout, if implemented.
+    fn register(&self, address: u8) -> Option<usize> {
+        let address = usize::from(address);
+        if self.registers[IOCON] & IOCON_BANK != 0 {
+            let (bank, offset) = (address >> 4, address & 0x0F);
+            (bank < 2 && offset <= 0x0A).then_some(offset * 2 + bank)
+        } else {
+            (address < 0x16).then_some(address)
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        let register = self.register(self.pointer);
+        self.advance();
+        let Some(register) = register else {
+            return 0x00;
+        };
+        self.accesses.push((register, true));
+
+        let value = match register {
+            GPIO | 0x13 => self.port(register - GPIO),
+            register => self.registers[register],
+        };
+        if matches!(register, INTCAP | 0x11 | GPIO | 0x13) {
+            let bank = register & 1;
+            self.registers[INTF + bank] = 0x00;
+            self.relatch();
+        }
+        value
+    }
+
+    fn write(&mut self, value: u8) {
+        let register = self.register(self.pointer);
+        self.advance();
+        let Some(register) = register else {
+            return;
+        };
+        self.accesses.push((register, false));
+
+        match register {
+            INTF | 0x0F | INTCAP | 0x11 => {}
+            GPIO | 0x13 => self.registers[OLAT + register - GPIO] = value,
+            IOCON | 0x0B => {
+                self.registers[IOCON] = value & !1;
+                self.registers[IOCON + 1] = value & !1;
+            }
+            register => self.registers[register] = value,
+        }
+        self.relatch();
+    }
+
+    fn advance(&mut self) {
+        if self.registers[IOCON] & IOCON_SEQOP != 0 {
+            return;
+        }
+        self.pointer = if self.registers[IOCON] & IOCON_BANK != 0 {
+            (self.pointer + 1) % 0x20
+        } else {
+            (self.pointer + 1) % 0x16
+        };
+    }
+}
+
+impl ErrorType for SimulatedBus {
+    type Error = ErrorKind;
+}
+
+impl I2c for SimulatedBus {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut devices = self.devices.lock().unwrap();
+        let Some(device) = devices.iter_mut().find(|device| device.address == address) else {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        };
+
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    if let Some((pointer, values)) = bytes.split_first() {
+                        device.pointer = *pointer;
+                        for value in values {
+                            device.write(*value);
+                        }
+                    }
+                }
+                Operation::Read(bytes) => {
+                    for byte in bytes.iter_mut() {
+                        *byte = device.read();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl digital::ErrorType for ResetPin {
+    type Error = digital::ErrorKind;
+}
+
+impl OutputPin for ResetPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.bus.device(self.address, Device::reset);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl digital::ErrorType for InterruptPin {
+    type Error = digital::ErrorKind;
+}
+
+impl InputPin for InterruptPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.is_low().map(|low| !low)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self
+            .bus
+            .device(self.address, |device| device.interrupt(self.bank)))
+    }
+}
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _: u32) {}
+}
@@ -0,0 +1,386 @@
+//! Interrupt handling against the registers of a simulated device.
+
+mod common;
+
+use common::{InterruptPin, SimulatedBus, DEFVAL, GPINTEN, GPIO, INTCON};
+use mcp23017_driver::error::Error;
+use mcp23017_driver::pin::interrupt::{Pending, Sense, Statistics};
+use mcp23017_driver::pin::{Bank, A0, B0};
+use mcp23017_driver::Mcp23017;
+
+#[test]
+fn storm_protection_masks_before_clearing() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+    interrupt_controller.set_storm_protection(true);
+
+    let b0 = pins
+        .b0
+        .into_pull_up_input()?
+        .enable_interrupt(Sense::Low, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.drive(1, 0xFE));
+    interrupt_controller.interrupt(Bank::B)?;
+
+    assert!(!bus.device(0x20, |device| device.interrupt(1)));
+    assert!(b0.is_masked());
+    assert_eq!(interrupt_controller.storms::<B0>(), 1);
+    assert_eq!(interrupt_controller.triggered(&b0), Some(false));
+    Ok(())
+}
+
+#[test]
+fn storm_protection_bounds_service() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, interrupt_controller) = device.split()?;
+    let mut interrupt_controller = interrupt_controller.with_interrupt_pins(
+        InterruptPin {
+            bus: bus.clone(),
+            address: 0x20,
+            bank: 0,
+        },
+        InterruptPin {
+            bus: bus.clone(),
+            address: 0x20,
+            bank: 1,
+        },
+    );
+    interrupt_controller.set_storm_protection(true);
+
+    let _b0 = pins
+        .b0
+        .into_pull_up_input()?
+        .enable_interrupt(Sense::Low, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.drive(1, 0xFE));
+    interrupt_controller.service()?;
+
+    let statistics = interrupt_controller.statistics::<B0>();
+    assert_eq!(
+        statistics,
+        Statistics {
+            seen: 1,
+            coalesced: 0,
+            max_depth: 1,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn rearm_unmasks_idle_pins() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+    interrupt_controller.set_storm_protection(true);
+
+    let a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    let b0 = pins
+        .b0
+        .into_pull_up_input()?
+        .enable_interrupt(Sense::Low, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.drive(1, 0xFE));
+    interrupt_controller.interrupt(Bank::B)?;
+    assert_eq!(interrupt_controller.triggered(&b0), Some(false));
+
+    // Still at its triggering level, so kept masked.
+    interrupt_controller.rearm()?;
+    assert!(b0.is_masked());
+
+    // An interrupt pending on another pin when rearming is not lost.
+    bus.device(0x20, |device| {
+        device.drive(0, 0x01);
+        device.drive(1, 0xFF);
+    });
+    interrupt_controller.rearm()?;
+    assert!(!b0.is_masked());
+    assert_eq!(
+        bus.device(0x20, |device| device.registers[GPINTEN + 1]),
+        0x01
+    );
+    assert_eq!(interrupt_controller.triggered(&a0), Some(true));
+    assert_eq!(interrupt_controller.triggered(&b0), None);
+    Ok(())
+}
+
+#[test]
+fn reports_port_interrupts() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (port_a, _port_b, mut interrupt_controller) = device.split_ports()?;
+    let port_a = port_a.enable_interrupt(Sense::Edge, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.drive(0, 0b0000_0100));
+    interrupt_controller.interrupt(Bank::A)?;
+    bus.device(0x20, |device| device.drive(0, 0b0000_0000));
+    interrupt_controller.interrupt(Bank::A)?;
+    bus.device(0x20, |device| device.drive(0, 0b0010_0000));
+    interrupt_controller.interrupt(Bank::A)?;
+
+    assert_eq!(
+        interrupt_controller.triggered_port(&port_a),
+        (0b0010_0100, 0b0010_0000)
+    );
+    assert_eq!(interrupt_controller.triggered_port(&port_a), (0, 0));
+    Ok(())
+}
+
+#[test]
+fn poll_detects_changes_and_levels() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    let b0 = pins
+        .b0
+        .into_pull_up_input()?
+        .enable_interrupt(Sense::Low, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.accesses.clear());
+    interrupt_controller.poll()?;
+    assert_eq!(interrupt_controller.triggered(&a0), None);
+    assert_eq!(interrupt_controller.triggered(&b0), None);
+
+    // Both banks are read in a single transaction, and nothing is written.
+    assert_eq!(
+        bus.device(0x20, |device| device.accesses.clone()),
+        [(GPIO, true), (GPIO + 1, true)]
+    );
+
+    bus.device(0x20, |device| device.drive(0, 0x01));
+    interrupt_controller.poll()?;
+    assert_eq!(interrupt_controller.triggered(&a0), Some(true));
+    interrupt_controller.poll()?;
+    assert_eq!(interrupt_controller.triggered(&a0), None);
+
+    bus.device(0x20, |device| device.drive(1, 0xFE));
+    for _ in 0..2 {
+        interrupt_controller.poll()?;
+        assert_eq!(interrupt_controller.triggered(&b0), Some(false));
+    }
+    Ok(())
+}
+
+#[test]
+fn poll_masks_and_rearms_storming_pins() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+    interrupt_controller.set_storm_protection(true);
+
+    let b0 = pins
+        .b0
+        .into_pull_up_input()?
+        .enable_interrupt(Sense::Low, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.drive(1, 0xFE));
+    interrupt_controller.poll()?;
+    assert_eq!(interrupt_controller.triggered(&b0), Some(false));
+    assert!(b0.is_masked());
+
+    interrupt_controller.poll()?;
+    assert_eq!(interrupt_controller.triggered(&b0), None);
+    assert_eq!(interrupt_controller.storms::<B0>(), 1);
+
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    interrupt_controller.poll()?;
+    assert!(!b0.is_masked());
+    assert_eq!(
+        bus.device(0x20, |device| device.registers[GPINTEN + 1]),
+        0x01
+    );
+    Ok(())
+}
+
+#[test]
+fn set_sense_keeps_interrupts_enabled() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, _interrupt_controller) = device.split()?;
+
+    let mut b0 = pins
+        .b0
+        .into_pull_up_input()?
+        .enable_interrupt(Sense::Edge, Pending::Discard)?;
+
+    bus.device(0x20, |device| device.accesses.clear());
+    b0.set_sense(Sense::Low)?;
+    assert_eq!(b0.sense(), Sense::Low);
+
+    bus.device(0x20, |device| {
+        assert_eq!(
+            device.accesses,
+            [
+                (DEFVAL, false),
+                (DEFVAL + 1, false),
+                (INTCON, false),
+                (INTCON + 1, false),
+            ]
+        );
+        assert_eq!(device.registers[GPINTEN + 1], 0x01);
+        assert_eq!(device.registers[INTCON + 1], 0x01);
+        assert_eq!(device.registers[DEFVAL + 1], 0x01);
+
+        device.drive(1, 0xFE);
+        assert!(device.interrupt(1));
+    });
+    Ok(())
+}
+
+#[test]
+fn masked_pins_do_not_interrupt() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let mut a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    a0.mask()?;
+    assert!(a0.is_masked());
+
+    bus.device(0x20, |device| {
+        assert_eq!(device.registers[GPINTEN], 0x00);
+        device.drive(0, 0x01);
+        assert!(!device.interrupt(0));
+    });
+
+    a0.unmask()?;
+    assert!(!a0.is_masked());
+    bus.device(0x20, |device| {
+        device.drive(0, 0x00);
+        assert!(device.interrupt(0));
+    });
+    interrupt_controller.interrupt(Bank::A)?;
+    assert_eq!(interrupt_controller.triggered(&a0), Some(false));
+    Ok(())
+}
+
+#[test]
+fn enabling_discards_stale_interrupts() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    // One interrupt recorded by the controller, and another still latched
+    // on the device, both from before the pin was disabled.
+    let a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Keep)?;
+    bus.device(0x20, |device| device.drive(0, 0x01));
+    interrupt_controller.interrupt(Bank::A)?;
+    bus.device(0x20, |device| device.drive(0, 0x00));
+    let a0 = a0.disable_interrupt()?;
+
+    let a0 = a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    assert!(!bus.device(0x20, |device| device.interrupt(0)));
+    interrupt_controller.interrupt(Bank::A)?;
+    assert_eq!(interrupt_controller.triggered(&a0), None);
+    Ok(())
+}
+
+#[test]
+fn enabling_keeps_interrupts_on_request() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Keep)?;
+    bus.device(0x20, |device| device.drive(0, 0x01));
+    let a0 = a0.disable_interrupt()?;
+
+    let a0 = a0.enable_interrupt(Sense::Edge, Pending::Keep)?;
+    interrupt_controller.interrupt(Bank::A)?;
+    assert_eq!(interrupt_controller.triggered(&a0), Some(true));
+    Ok(())
+}
+
+#[test]
+fn discarding_keeps_other_pins() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, interrupt_controller) = device.split()?;
+
+    let a1 = pins.a1.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    bus.device(0x20, |device| device.drive(0, 0x02));
+
+    let a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    assert_eq!(interrupt_controller.triggered(&a1), Some(true));
+    assert_eq!(interrupt_controller.triggered(&a0), None);
+    Ok(())
+}
+
+#[test]
+fn statistics_count_coalesced_interrupts() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    for level in [0x01, 0x00, 0x01] {
+        bus.device(0x20, |device| device.drive(0, level));
+        interrupt_controller.interrupt(Bank::A)?;
+    }
+    assert_eq!(interrupt_controller.triggered(&a0), Some(true));
+
+    bus.device(0x20, |device| device.drive(0, 0x00));
+    interrupt_controller.interrupt(Bank::A)?;
+    assert_eq!(interrupt_controller.triggered(&a0), Some(false));
+
+    assert_eq!(
+        interrupt_controller.statistics::<A0>(),
+        Statistics {
+            seen: 4,
+            coalesced: 2,
+            max_depth: 3,
+        }
+    );
+
+    interrupt_controller.reset_statistics();
+    assert_eq!(
+        interrupt_controller.statistics::<A0>(),
+        Statistics::default()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn statistics_count_interrupts_received_while_waiting() -> Result<(), Error<SimulatedBus>> {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    use embedded_hal_async::digital::Wait;
+
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let mut a0 = pins.a0.enable_interrupt(Sense::Edge, Pending::Discard)?;
+    let mut context = Context::from_waker(Waker::noop());
+    for level in [0x01, 0x00, 0x01] {
+        let mut edge = pin!(a0.wait_for_any_edge());
+        assert!(edge.as_mut().poll(&mut context).is_pending());
+
+        bus.device(0x20, |device| device.drive(0, level));
+        interrupt_controller.interrupt(Bank::A)?;
+        assert!(matches!(edge.poll(&mut context), Poll::Ready(Ok(()))));
+    }
+
+    assert_eq!(
+        interrupt_controller.statistics::<A0>(),
+        Statistics {
+            seen: 3,
+            coalesced: 0,
+            max_depth: 1,
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! Splitting and rejoining ports on simulated devices.
+
+mod common;
+
+use common::SimulatedBus;
+use mcp23017_driver::error::Error;
+use mcp23017_driver::Mcp23017;
+
+#[test]
+fn joins_pins_from_one_device() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20, 0x21]);
+    let mut first = Mcp23017::<_, 0x20>::new(bus.clone());
+    let mut second = Mcp23017::<_, 0x21>::new(bus.clone());
+    let (first, _, _) = first.split_ports()?;
+    let (second, _, _) = second.split_ports()?;
+
+    let mut first = first.split();
+    let mut second = second.split();
+    core::mem::swap(&mut first.p3, &mut second.p3);
+
+    let Err(mut first) = first.join() else {
+        panic!("pins from different devices were joined");
+    };
+    let Err(mut second) = second.join() else {
+        panic!("pins from different devices were joined");
+    };
+
+    core::mem::swap(&mut first.p3, &mut second.p3);
+    assert!(first.join().is_ok());
+    assert!(second.join().is_ok());
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Detection of devices in either register layout on a simulated bus.
+
+mod common;
+
+use common::{SimulatedBus, GPINTEN, GPPU, OLAT};
+use mcp23017_driver::error::Error;
+use mcp23017_driver::probe::{probe, scan};
+use mcp23017_driver::Mcp23017;
+
+#[test]
+fn detects_paired_layout() -> Result<(), Error<SimulatedBus>> {
+    let mut bus = SimulatedBus::new(&[0x20]);
+
+    let iocon = probe(&mut bus, 0x20)?.expect("device not detected");
+    assert_eq!(iocon.bits(), 0x00);
+    assert!(!iocon.bank());
+    Ok(())
+}
+
+#[test]
+fn detects_segregated_layout() -> Result<(), Error<SimulatedBus>> {
+    let mut bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.set_banked());
+
+    for latch in [0x00, 0x5A] {
+        bus.device(0x20, |device| device.registers[OLAT] = latch);
+        let iocon = probe(&mut bus, 0x20)?.expect("device not detected");
+        assert!(iocon.bank());
+    }
+    Ok(())
+}
+
+#[test]
+fn distinguishes_paired_layout_resembling_segregated() -> Result<(), Error<SimulatedBus>> {
+    let mut bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| {
+        device.registers[GPINTEN + 1] = 0x80;
+        device.registers[OLAT + 1] = 0x80;
+        device.registers[GPPU] = 0x01;
+    });
+
+    let iocon = probe(&mut bus, 0x20)?.expect("device not detected");
+    assert!(!iocon.bank());
+    Ok(())
+}
+
+#[test]
+fn scans_both_layouts() -> Result<(), Error<SimulatedBus>> {
+    let mut bus = SimulatedBus::new(&[0x21, 0x26]);
+    bus.device(0x26, |device| device.set_banked());
+
+    let scan = scan(&mut bus)?;
+    let detected: Vec<_> = scan
+        .iter()
+        .map(|detected| (detected.address, detected.iocon.bank()))
+        .collect();
+    assert_eq!(detected, [(0x21, false), (0x26, true)]);
+    assert_eq!(scan.get(0x20), None);
+    Ok(())
+}
+
+#[test]
+fn attach_rejects_segregated_layout() {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.set_banked());
+
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    assert!(matches!(device.attach(), Err(Error::Unrecognized)));
+    bus.device(0x20, |device| {
+        assert!(device.accesses.iter().all(|&(_, read)| read));
+    });
+}
@@ -0,0 +1,87 @@
+//! Recovery of pin configuration after a device reset on a simulated bus.
+
+mod common;
+
+use common::{InterruptPin, NoDelay, ResetPin, SimulatedBus, IODIR, OLAT};
+use embedded_hal::digital::OutputPin;
+use mcp23017_driver::error::Error;
+use mcp23017_driver::pin::release::Release;
+use mcp23017_driver::Mcp23017;
+
+#[test]
+fn restores_pins_modified_after_reset() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let mut a0 = pins.a0.into_push_pull_output()?;
+    let mut a1 = pins.a1.into_push_pull_output()?;
+    a0.set_high()?;
+
+    // A brown-out returns the device to its power-on configuration, so the
+    // output latches and directions read back cleared and as inputs.
+    bus.device(0x20, |device| device.reset());
+    a1.set_high()?;
+    interrupt_controller.restore()?;
+
+    bus.device(0x20, |device| {
+        assert_eq!(device.registers[OLAT], 0b0000_0011);
+        assert_eq!(device.registers[IODIR], 0b0111_1100);
+    });
+    Ok(())
+}
+
+#[test]
+fn hard_reset_restores_pins_modified_after_reset() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let reset = ResetPin {
+        bus: bus.clone(),
+        address: 0x20,
+    };
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone()).with_reset(reset, NoDelay);
+    let (pins, mut interrupt_controller) = device.split()?;
+
+    let mut b0 = pins.b0.into_push_pull_output()?;
+    let mut b1 = pins.b1.into_push_pull_output()?;
+    b0.set_high()?;
+
+    bus.device(0x20, |device| device.reset());
+    b1.set_high()?;
+    interrupt_controller.hard_reset()?;
+
+    bus.device(0x20, |device| {
+        assert_eq!(device.registers[OLAT + 1], 0b0000_0011);
+        assert_eq!(device.registers[IODIR + 1], 0b0111_1100);
+    });
+    Ok(())
+}
+
+#[test]
+fn release_returns_interrupt_pins() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    let mut device = Mcp23017::<_, 0x20>::new(bus.clone());
+    let (pins, interrupt_controller) = device.split()?;
+    let interrupt_controller = interrupt_controller.with_interrupt_pins(
+        InterruptPin {
+            bus: bus.clone(),
+            address: 0x20,
+            bank: 0,
+        },
+        InterruptPin {
+            bus: bus.clone(),
+            address: 0x20,
+            bank: 1,
+        },
+    );
+
+    let (interrupt_controller, a, b) = interrupt_controller.without_interrupt_pins();
+    pins.release(interrupt_controller, Release::Reset)?;
+    assert_eq!((a.bank, b.bank), (0, 1));
+
+    bus.device(0x20, |device| {
+        assert_eq!(device.registers[IODIR], 0xFF);
+        assert_eq!(device.registers[IODIR + 1], 0xFF);
+    });
+    device.free();
+    Ok(())
+}
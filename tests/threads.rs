@@ -0,0 +1,73 @@
+//! Pins moved into separate threads, sharing one expander on a simulated bus.
+//!
+//! The driver is placed in [`Storage`], so the pins split from it are
+//! `'static` and can be moved into threads. One thread blinks an output while
+//! another waits for a button press on an input, which the main thread
+//! simulates.
+
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use common::SimulatedBus;
+use embedded_hal::digital::{InputPin, StatefulOutputPin};
+use mcp23017_driver::error::Error;
+use mcp23017_driver::storage::Storage;
+use mcp23017_driver::Mcp23017;
+
+static DEVICE: Storage<Mcp23017<SimulatedBus, 0x20>> = Storage::new();
+
+#[test]
+fn pins_in_threads() -> Result<(), Error<SimulatedBus>> {
+    let bus = SimulatedBus::new(&[0x20]);
+    bus.device(0x20, |device| device.drive(1, 0xFF));
+
+    let device = DEVICE.init(Mcp23017::new(bus.clone()));
+    let (pins, _interrupt_controller) = device.split()?;
+
+    let mut led = pins.a0.into_push_pull_output()?;
+    let mut button = pins.b0.into_pull_up_input()?;
+
+    let blinker = thread::spawn(move || -> Result<(), Error<SimulatedBus>> {
+        for _ in 0..100 {
+            retry(|| led.toggle())?;
+            thread::sleep(Duration::from_micros(50));
+        }
+        assert!(retry(|| led.is_set_low())?);
+        Ok(())
+    });
+
+    let watcher = thread::spawn(move || -> Result<u32, Error<SimulatedBus>> {
+        let mut polls = 0;
+        while retry(|| button.is_high())? {
+            polls += 1;
+            thread::sleep(Duration::from_micros(50));
+        }
+        Ok(polls)
+    });
+
+    thread::sleep(Duration::from_millis(2));
+    bus.device(0x20, |device| device.drive(1, 0xFE));
+
+    blinker.join().unwrap()?;
+    let polls = watcher.join().unwrap()?;
+    assert!(polls > 0);
+    bus.device(0x20, |device| {
+        assert_eq!(device.registers[common::OLAT] & 1, 0)
+    });
+
+    Ok(())
+}
+
+/// Retry an operation for as long as the bus is in use by another thread.
+fn retry<T>(
+    mut operation: impl FnMut() -> Result<T, Error<SimulatedBus>>,
+) -> Result<T, Error<SimulatedBus>> {
+    loop {
+        match operation() {
+            Err(Error::Busy) => thread::yield_now(),
+            result => return result,
+        }
+    }
+}